@@ -1,8 +1,14 @@
+use crate::config::DeviceRules;
+use crate::server::atoms::atom_helper_pairing;
+use crate::server::molecules::path_check::PathChecker;
 use crate::server::{self, UiEvent};
+use crate::settings;
 use crate::update;
+use iced::widget::{qr_code, text_editor};
 use iced::{Command, Size};
-use tokio::sync::oneshot;
+use tokio::sync::{mpsc, oneshot};
 
+use super::atom_helper_config;
 use super::atom_helper_log;
 use super::atom_helper_path;
 use super::data_officer;
@@ -63,25 +69,77 @@ pub fn handle_ui_event(app: &mut F11App, ev: UiEvent) {
         UiEvent::Log { level, message } => {
             push_log(app, format!("[{level}] {message}"));
         }
-        UiEvent::Running { addr } => {
+        UiEvent::Running { addr, urls } => {
             app.running = true;
             app.stopping = false;
             push_log(app, format!("[info] 服务器已启动: http://{addr}"));
+            app.pair_qr = urls.first().and_then(|url| {
+                let template = atom_helper_config::parse_pair_url_template(&app.pair_url_template);
+                let payload = atom_helper_pairing::pairing_payload(url, template.as_deref());
+                qr_code::Data::new(payload).ok()
+            });
+            app.lan_urls = urls;
         }
         UiEvent::Stopped => {
             app.running = false;
             app.stopping = false;
             app.server = None;
             app.connected = false;
+            app.client_device = None;
+            app.pending_takeover = None;
+            app.pending_device = None;
+            app.sync_progress = None;
+            app.tree_size = None;
+            app.lan_urls.clear();
+            app.pair_qr = None;
             push_log(app, "[warn] 服务器已停止".to_string());
             app.touch_config();
         }
-        UiEvent::ClientConnected => {
+        UiEvent::ClientConnected(device) => {
             app.connected = true;
-            push_log(app, "[info] 客户端已连接".to_string());
+            push_log(app, format!("[info] 客户端已连接: {device}"));
+            app.client_device = Some(device);
+        }
+        UiEvent::ClientIdentified(device) => {
+            app.client_device = Some(device);
+        }
+        UiEvent::DeviceApprovalRequested(device) => {
+            app.pending_device = Some(device);
+        }
+        UiEvent::DeviceApprovalResolved => {
+            app.pending_device = None;
+        }
+        UiEvent::DeviceTrusted(device) => {
+            app.trusted_devices.retain(|d| d.id != device.id);
+            app.trusted_devices.push(device);
+            app.touch_config();
+        }
+        UiEvent::DeviceRulesStored { id, rules } => {
+            app.device_rules.insert(id.clone(), rules);
+            if app.rules_device.as_deref() == Some(id.as_str()) {
+                select_device_rules(app, id);
+            }
+        }
+        UiEvent::TakeoverRequested(device) => {
+            app.pending_takeover = Some(device);
+        }
+        UiEvent::TakeoverResolved => {
+            app.pending_takeover = None;
+        }
+        UiEvent::SyncProgress { done, total } => {
+            app.sync_progress = (done < total).then_some((done, total));
+        }
+        UiEvent::TreeSize { files, bytes, over_quota } => {
+            app.tree_size = Some((files, bytes, over_quota));
+        }
+        UiEvent::ClientConfigured(cfg) => {
+            app.client_config = Some(cfg);
         }
         UiEvent::ClientDisconnected => {
             app.connected = false;
+            app.client_device = None;
+            app.client_config = None;
+            app.sync_progress = None;
             push_log(app, "[warn] 客户端已断开".to_string());
         }
     }
@@ -119,18 +177,20 @@ pub fn apply_dir_input(app: &mut F11App) {
     }
 }
 
+pub fn refresh_regex_issues(app: &mut F11App) {
+    app.regex_issues = atom_helper_config::server_side_regex_issues(&app.server_side_regex);
+}
+
 pub fn persist_config_if_valid(app: &mut F11App) {
-    let Some(cfg) = data_officer::build_server_config(
-        &app.host,
-        &app.port,
-        &app.dir,
-        &app.server_side_regex,
-        app.enable_size_limit,
-        &app.max_size,
-    ) else {
+    let Some(cfg) = data_officer::build_server_config(app) else {
         return;
     };
 
+    // 规则有误时不下发，服务端继续使用上一次的有效规则
+    if let (Some(server), true) = (&app.server, app.regex_issues.is_empty()) {
+        server.reconfigure(cfg.clone());
+    }
+
     let app_cfg = data_officer::build_app_config(
         &cfg,
         &app.server_side_regex,
@@ -145,6 +205,126 @@ pub fn persist_config_if_valid(app: &mut F11App) {
     }
 }
 
+fn apply_server_settings(app: &mut F11App, cfg: &settings::ServerConfigFile) {
+    app.host = cfg.host.to_string();
+    app.port = cfg.port.to_string();
+    app.dir = std::fs::canonicalize(&cfg.dir).unwrap_or_else(|_| cfg.dir.clone());
+    app.dir_input = atom_helper_path::display_path(&app.dir);
+    app.server_side_regex = text_editor::Content::with_text(&cfg.server_side_path_regex.join("\n"));
+    app.enable_size_limit = cfg.enable_file_size_limit;
+    app.max_size = cfg.max_file_size.to_string();
+    app.quota_bytes = cfg.quota_bytes.map(|v| v.to_string()).unwrap_or_default();
+    app.quota_files = cfg.quota_files.map(|v| v.to_string()).unwrap_or_default();
+    app.size_rules = text_editor::Content::with_text(
+        &cfg.size_rules.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"),
+    );
+    app.pair_url_template = cfg.pair_url_template.clone().unwrap_or_default();
+    app.resume_grace = cfg.resume_grace_secs.to_string();
+    app.takeover = cfg.takeover;
+    app.require_device_approval = cfg.require_device_approval;
+    app.trusted_devices = cfg.trusted_devices.clone();
+    app.device_rules = cfg.device_rules.clone();
+    app.rules_device = None;
+    refresh_regex_issues(app);
+}
+
+pub fn switch_profile(app: &mut F11App, name: String) {
+    if app.running || app.stopping {
+        app.last_error = "请先停止服务再切换配置".to_string();
+        return;
+    }
+    if name == app.active_profile {
+        return;
+    }
+
+    persist_config_if_valid(app);
+    let previous = app.active_profile.clone();
+    if let Err(err) = diplomat::switch_profile(&name) {
+        app.last_error = format!("切换配置失败: {err:#}");
+        return;
+    }
+
+    match diplomat::load_settings() {
+        Ok(Some(cfg)) => apply_server_settings(app, &cfg.server),
+        // 新 profile：以当前设置为起点
+        Ok(None) => persist_config_if_valid(app),
+        Err(err) => {
+            let _ = diplomat::switch_profile(&previous);
+            app.last_error = format!("读取配置 {name} 失败: {err:#}");
+            return;
+        }
+    }
+
+    app.active_profile = settings::active_profile();
+    app.profiles = settings::list_profiles();
+    app.last_error.clear();
+    push_log(app, format!("[info] 切换配置: {}", app.active_profile));
+}
+
+pub fn create_profile(app: &mut F11App) {
+    let name = app.new_profile_name.trim().to_string();
+    if name.is_empty() {
+        app.last_error = "请输入配置名称".to_string();
+        return;
+    }
+    switch_profile(app, name);
+    if app.last_error.is_empty() {
+        app.new_profile_name.clear();
+    }
+}
+
+fn path_checker(app: &mut F11App) -> Option<PathChecker> {
+    let Some(cfg) = data_officer::build_server_config(app) else {
+        app.last_error = "配置不合法（Host/Port/目录）".to_string();
+        return None;
+    };
+    let client = app.client_config.clone().unwrap_or_default();
+    Some(PathChecker::new(cfg, client))
+}
+
+pub fn test_path(app: &mut F11App) {
+    let rel = app.path_test_input.trim().to_string();
+    if rel.is_empty() {
+        return;
+    }
+    let Some(checker) = path_checker(app) else { return };
+    let report = checker.check_rel(&rel);
+    let kind = if report.is_dir { "目录" } else { "文件" };
+    app.path_test_results = vec![format!("{rel} [{kind}] {}", report.verdict.describe())];
+}
+
+pub fn dry_run_tree(app: &mut F11App) -> Command<Message> {
+    if app.dry_running {
+        return Command::none();
+    }
+    let Some(checker) = path_checker(app) else {
+        return Command::none();
+    };
+    let dir = app.dir.clone();
+    app.dry_running = true;
+    app.path_test_results = vec!["正在预演…".to_string()];
+    Command::perform(
+        async move {
+            tokio::task::spawn_blocking(move || {
+                let preview = checker.preview(&dir);
+                let skipped = &preview.skipped;
+                let mut lines = vec![preview.summary()];
+                lines.extend(skipped.iter().take(200).map(|r| {
+                    let suffix = if r.is_dir { "/" } else { "" };
+                    format!("{}{suffix} {}", r.rel, r.verdict.describe())
+                }));
+                if skipped.len() > 200 {
+                    lines.push(format!("…其余 {} 项未显示", skipped.len() - 200));
+                }
+                lines
+            })
+            .await
+            .unwrap_or_else(|err| vec![format!("预演失败: {err}")])
+        },
+        Message::DryRunDone,
+    )
+}
+
 pub fn quit(app: &mut F11App) -> Command<Message> {
     persist_config_if_valid(app);
     if let Some(server) = &mut app.server {
@@ -181,14 +361,12 @@ pub fn start_stop(app: &mut F11App) -> Command<Message> {
         }
     }
 
-    let Some(cfg) = data_officer::build_server_config(
-        &app.host,
-        &app.port,
-        &app.dir,
-        &app.server_side_regex,
-        app.enable_size_limit,
-        &app.max_size,
-    ) else {
+    if !app.regex_issues.is_empty() {
+        app.last_error = "过滤规则不合法，请先在高级设置中修正".to_string();
+        return Command::none();
+    }
+
+    let Some(cfg) = data_officer::build_server_config(app) else {
         app.last_error = "配置不合法（Host/Port/目录）".to_string();
         return Command::none();
     };
@@ -200,11 +378,18 @@ pub fn start_stop(app: &mut F11App) -> Command<Message> {
 
     let ui_tx = app.ui_tx.clone();
     let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let (cmd_tx, cmd_rx) = mpsc::unbounded_channel::<server::ServerCommand>();
     std::thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new();
         match rt {
             Ok(rt) => {
-                let _ = rt.block_on(server::run_server(cfg, shutdown_rx, Some(ui_tx)));
+                if let Err(err) = rt.block_on(server::run_server(cfg, shutdown_rx, Some(ui_tx.clone()), Some(cmd_rx))) {
+                    let _ = ui_tx.send(UiEvent::Log {
+                        level: "error",
+                        message: format!("服务启动失败: {err:#}"),
+                    });
+                    let _ = ui_tx.send(UiEvent::Stopped);
+                }
             }
             Err(err) => {
                 let _ = ui_tx.send(UiEvent::Log {
@@ -217,12 +402,89 @@ pub fn start_stop(app: &mut F11App) -> Command<Message> {
 
     app.server = Some(BgServer {
         shutdown: Some(shutdown_tx),
+        commands: cmd_tx,
     });
     app.running = true;
     push_log(app, "[info] 正在启动服务器...".to_string());
     Command::batch(commands)
 }
 
+pub fn push_all(app: &mut F11App) -> Command<Message> {
+    let Some(server) = &app.server else {
+        return Command::none();
+    };
+    if !app.connected {
+        app.last_error = "客户端未连接，无法推送".to_string();
+        return Command::none();
+    }
+    server.push_all();
+    app.last_error.clear();
+    push_log(app, "[info] 请求推送全部文件到手机...".to_string());
+    ensure_logs_sidebar_visible(app).unwrap_or_else(Command::none)
+}
+
+pub fn request_upload(app: &mut F11App) -> Command<Message> {
+    let Some(server) = &app.server else {
+        return Command::none();
+    };
+    if !app.connected {
+        app.last_error = "客户端未连接，无法请求上传".to_string();
+        return Command::none();
+    }
+    server.request_upload();
+    app.last_error.clear();
+    push_log(app, "[info] 请求手机上传全部文件（先为同步目录拍快照）...".to_string());
+    ensure_logs_sidebar_visible(app).unwrap_or_else(Command::none)
+}
+
+pub fn disconnect_client(app: &mut F11App) {
+    let Some(server) = &app.server else { return };
+    server.disconnect_client();
+    app.last_error.clear();
+}
+
+/// 允许（替换当前客户端）或拒绝等待确认的新连接。
+pub fn resolve_takeover(app: &mut F11App, accept: bool) {
+    let Some(server) = &app.server else { return };
+    server.resolve_takeover(accept);
+    app.pending_takeover = None;
+}
+
+/// 允许（`remember` 时同时信任）或拒绝等待确认的未知设备。
+pub fn resolve_device(app: &mut F11App, accept: bool, remember: bool) {
+    let Some(server) = &app.server else { return };
+    server.resolve_device(accept, remember);
+    app.pending_device = None;
+}
+
+pub fn forget_device(app: &mut F11App, id: &str) {
+    app.trusted_devices.retain(|d| d.id != id);
+    app.touch_config();
+}
+
+pub fn select_device_rules(app: &mut F11App, id: String) {
+    let rules = app.device_rules.get(&id).cloned().unwrap_or_default();
+    app.rules_max_size = rules.max_file_size.map(|v| v.to_string()).unwrap_or_default();
+    app.rules_regex = text_editor::Content::with_text(&rules.path_regex.unwrap_or_default().join("\n"));
+    app.rules_device = Some(id);
+}
+
+/// 修改正在编辑的设备规则；该设备已连接时由服务端立即应用。
+pub fn edit_device_rules(app: &mut F11App, edit: impl FnOnce(&mut DeviceRules)) {
+    let Some(rules) = app.rules_device.as_ref().and_then(|id| app.device_rules.get_mut(id)) else {
+        return;
+    };
+    edit(rules);
+    app.touch_config();
+}
+
+pub fn forget_device_rules(app: &mut F11App) {
+    if let Some(id) = app.rules_device.take() {
+        app.device_rules.remove(&id);
+        app.touch_config();
+    }
+}
+
 pub fn collapse_logs_sidebar(app: &mut F11App) -> Command<Message> {
     if app.show_logs_sidebar {
         app.show_logs_sidebar = false;
@@ -23,6 +23,9 @@
 //! - `pub fn save_settings(app_cfg: &settings::AppConfig) -> anyhow::Result<()>`
 //!   - 副作用：写入配置文件（YAML）
 //!   - 错误：IO/序列化错误
+//! - `pub fn load_settings() -> anyhow::Result<Option<settings::AppConfig>>` / `switch_profile(name)`
+//!   - 副作用：读取当前 profile 的配置文件 / 切换并记住当前 profile
+//!   - 错误：IO/解析错误、profile 名称不合法
 //! - `pub fn init_tray() -> Option<TrayHandle>`
 //!   - 副作用：创建系统托盘图标与菜单
 //!   - 错误：资源缺失/创建失败返回 `None`
@@ -34,8 +37,8 @@
 //! L2 · Data Officer（校验/归一化/结构转换）
 //! ---------------------------------------------------------------------------
 //! `data_officer.rs`
-//! - `pub fn build_server_config(...) -> Option<ServerConfig>`
-//!   - 输入：GUI 文本输入（host/port/max_size）+ 目录 + 规则编辑器内容
+//! - `pub fn build_server_config(app: &F11App) -> Option<ServerConfig>`
+//!   - 输入：GUI 文本输入（host/port/max_size/断线保留秒数）+ 新连接策略 + 目录 + 规则编辑器内容
 //!   - 输出：可运行的 `ServerConfig`（失败返回 `None`）
 //!   - 副作用：无
 //! - `pub fn build_app_config(...) -> settings::AppConfig`
@@ -48,7 +51,15 @@
 //! ---------------------------------------------------------------------------
 //! `actions.rs`
 //! - `start_stop`：启动/停止服务端线程，必要时展开日志侧边栏（窗口 resize）
+//! - `persist_config_if_valid`：延迟保存配置；服务运行中时同时通过 `BgServer::reconfigure` 下发给服务端热更新
 //! - `browse_dir` / `apply_dir_input`：目录选择/应用（会触发写配置延迟保存）
+//! - `switch_profile` / `create_profile`：保存当前配置后切换 profile，并把新 profile 的服务端设置回填到界面
+//! - `push_all`：通过 `BgServer::push_all` 让服务端主动推送全部文件（按钮与托盘菜单共用）
+//! - `request_upload`：通过 `BgServer::request_upload` 请求客户端上传全部（服务端先拍快照；按钮与托盘菜单共用）
+//! - `disconnect_client` / `resolve_takeover`：断开当前客户端、允许或拒绝等待确认的新连接（`UiEvent::TakeoverRequested` 时显示）
+//! - `resolve_device(app, accept, remember)` / `forget_device(app, id)`：处理等待确认的未知设备（`UiEvent::DeviceApprovalRequested` 时显示）、从信任列表移除设备
+//! - `select_device_rules` / `edit_device_rules` / `forget_device_rules`：查看、修改、删除按设备记住的客户端规则（`UiEvent::DeviceRulesStored` 时刷新）
+//! - `test_path` / `dry_run_tree`：规则测试与整棵目录预演（连接时包含客户端规则；预演在阻塞线程池中执行）
 //! - `check_update` / `download_update`：检查/下载更新（异步执行，结果回写日志）
 //! - `copy_logs`：写剪贴板（副作用：剪贴板）
 //! - `export_logs`：保存日志到文件（副作用：文件系统）
@@ -58,8 +69,13 @@
 //! ---------------------------------------------------------------------------
 //! `atom_helper_config.rs`
 //! - `parse_server_side_path_regex(content: &text_editor::Content) -> Vec<String>`
+//! - `server_side_regex_issues(content: &text_editor::Content) -> Vec<String>`（按编辑器行号提示非法规则）
+//! - `parse_size_rules(content) -> Vec<SizeRule>` / `size_rule_issues(content) -> Vec<String>`（每行 `模式=最大字节数`，非法行跳过并提示）
 //! - `parse_host_port(host: &str, port: &str) -> Option<(IpAddr, u16)>`
 //! - `parse_max_server_side_file_size(max_size: &str) -> u64`
+//! - `parse_pair_url_template(template: &str) -> Option<String>`
+//! - `parse_resume_grace_secs(raw: &str) -> u64`（非法时按 60 秒）
+//! - `parse_quota(raw: &str) -> Option<u64>`（留空或非法表示不限制）
 //!
 //! `atom_helper_log.rs`
 //! - 规范化日志行（去空/截断/统一格式），不做 IO
@@ -1,3 +1,5 @@
+use crate::config::SizeRule;
+use crate::server::atoms::atom_helper_filter;
 use iced::widget::text_editor;
 use std::net::IpAddr;
 
@@ -10,6 +12,37 @@ pub fn parse_server_side_path_regex(content: &text_editor::Content) -> Vec<Strin
         .collect::<Vec<_>>()
 }
 
+/// 编辑器中的非法规则，按编辑器行号（含空行）提示。
+pub fn server_side_regex_issues(content: &text_editor::Content) -> Vec<String> {
+    content
+        .text()
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| {
+            let pattern = line.trim();
+            regex::Regex::new(pattern)
+                .err()
+                .map(|err| format!("第 {} 行 `{pattern}`: {}", i + 1, atom_helper_filter::regex_error_summary(&err)))
+        })
+        .collect()
+}
+
+/// 大小规则编辑器：每行 `模式=最大字节数`，非法行跳过（由 `size_rule_issues` 提示）。
+pub fn parse_size_rules(content: &text_editor::Content) -> Vec<SizeRule> {
+    content.text().lines().filter_map(|line| line.trim().parse().ok()).collect()
+}
+
+pub fn size_rule_issues(content: &text_editor::Content) -> Vec<String> {
+    content
+        .text()
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| line.trim().parse::<SizeRule>().err().map(|err| format!("第 {} 行: {err}", i + 1)))
+        .collect()
+}
+
 pub fn parse_host_port(host: &str, port: &str) -> Option<(IpAddr, u16)> {
     let host: IpAddr = host.parse().ok()?;
     let port: u16 = port.parse().ok()?;
@@ -19,3 +52,17 @@ pub fn parse_host_port(host: &str, port: &str) -> Option<(IpAddr, u16)> {
 pub fn parse_max_server_side_file_size(max_size: &str) -> u64 {
     max_size.parse().ok().unwrap_or(250 * 1024)
 }
+
+/// 配额输入：留空或非法时不限制。
+pub fn parse_quota(raw: &str) -> Option<u64> {
+    raw.trim().parse().ok()
+}
+
+pub fn parse_resume_grace_secs(raw: &str) -> u64 {
+    raw.trim().parse().ok().unwrap_or(60)
+}
+
+pub fn parse_pair_url_template(template: &str) -> Option<String> {
+    let trimmed = template.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
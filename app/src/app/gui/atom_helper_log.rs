@@ -33,7 +33,7 @@ pub fn normalize_log_line(line: impl Into<String>) -> Option<String> {
     }
     if line.len() > 2000 {
         line.truncate(2000);
-        line.push_str("…");
+        line.push('…');
     }
     if let std::borrow::Cow::Owned(v) = atom_helper_path::normalize_windows_path_prefixes_in_text(&line) {
         line = v;
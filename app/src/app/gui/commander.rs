@@ -2,7 +2,7 @@ use iced::widget::scrollable::RelativeOffset;
 use iced::Command;
 use std::time::Duration as StdDuration;
 
-use super::{actions, F11App, Message};
+use super::{actions, atom_helper_config, F11App, Message};
 
 pub fn update(app: &mut F11App, message: Message) -> Command<Message> {
     match message {
@@ -96,14 +96,124 @@ pub fn update(app: &mut F11App, message: Message) -> Command<Message> {
             app.touch_config();
             Command::none()
         }
+        Message::QuotaBytesChanged(v) => {
+            app.quota_bytes = v;
+            app.touch_config();
+            Command::none()
+        }
+        Message::QuotaFilesChanged(v) => {
+            app.quota_files = v;
+            app.touch_config();
+            Command::none()
+        }
+        Message::PairUrlTemplateChanged(v) => {
+            app.pair_url_template = v;
+            app.touch_config();
+            Command::none()
+        }
+        Message::ResumeGraceChanged(v) => {
+            app.resume_grace = v;
+            app.touch_config();
+            Command::none()
+        }
+        Message::TakeoverPolicySelected(v) => {
+            app.takeover = v;
+            app.touch_config();
+            Command::none()
+        }
+        Message::DisconnectClient => {
+            actions::disconnect_client(app);
+            Command::none()
+        }
+        Message::ResolveTakeover(accept) => {
+            actions::resolve_takeover(app, accept);
+            Command::none()
+        }
+        Message::ToggleRequireDeviceApproval(v) => {
+            app.require_device_approval = v;
+            app.touch_config();
+            Command::none()
+        }
+        Message::ResolveDevice { accept, remember } => {
+            actions::resolve_device(app, accept, remember);
+            Command::none()
+        }
+        Message::ForgetDevice(id) => {
+            actions::forget_device(app, &id);
+            Command::none()
+        }
+        Message::DeviceRulesSelected(id) => {
+            actions::select_device_rules(app, id);
+            Command::none()
+        }
+        Message::DeviceRulesSizeLimit(v) => {
+            actions::edit_device_rules(app, |rules| rules.enable_file_size_limit = Some(v));
+            Command::none()
+        }
+        Message::DeviceRulesMaxSizeChanged(v) => {
+            // 留空表示不声明；非数字时只更新输入框
+            let parsed = v.trim().parse::<u64>().ok();
+            if v.trim().is_empty() || parsed.is_some() {
+                actions::edit_device_rules(app, |rules| rules.max_file_size = parsed);
+            }
+            app.rules_max_size = v;
+            Command::none()
+        }
+        Message::DeviceRulesRegexEdited(action) => {
+            let is_edit = action.is_edit();
+            app.rules_regex.perform(action);
+            if is_edit {
+                let list = atom_helper_config::parse_server_side_path_regex(&app.rules_regex);
+                actions::edit_device_rules(app, |rules| rules.path_regex = Some(list));
+            }
+            Command::none()
+        }
+        Message::ForgetDeviceRules => {
+            actions::forget_device_rules(app);
+            Command::none()
+        }
+        Message::PathTestInputChanged(v) => {
+            app.path_test_input = v;
+            Command::none()
+        }
+        Message::TestPath => {
+            actions::test_path(app);
+            Command::none()
+        }
+        Message::DryRunTree => actions::dry_run_tree(app),
+        Message::DryRunDone(lines) => {
+            app.dry_running = false;
+            app.path_test_results = lines;
+            Command::none()
+        }
+        Message::ProfileSelected(name) => {
+            actions::switch_profile(app, name);
+            Command::none()
+        }
+        Message::NewProfileNameChanged(v) => {
+            app.new_profile_name = v;
+            Command::none()
+        }
+        Message::CreateProfile => {
+            actions::create_profile(app);
+            Command::none()
+        }
+        Message::SizeRulesEdited(action) => {
+            app.size_rules.perform(action);
+            app.touch_config();
+            Command::none()
+        }
         Message::ServerRegexEdited(action) => {
             app.server_side_regex.perform(action);
+            actions::refresh_regex_issues(app);
             app.touch_config();
             Command::none()
         }
         Message::StartStop => {
             actions::start_stop(app)
         }
+        Message::PushAll => actions::push_all(app),
+        Message::RequestUpload => actions::request_upload(app),
         Message::CollapseLogsSidebar => {
             actions::collapse_logs_sidebar(app)
         }
@@ -143,6 +253,12 @@ pub fn update(app: &mut F11App, message: Message) -> Command<Message> {
             if id == tray.start_stop_id {
                 return update(app, Message::StartStop);
             }
+            if id == tray.push_all_id {
+                return update(app, Message::PushAll);
+            }
+            if id == tray.request_upload_id {
+                return update(app, Message::RequestUpload);
+            }
             if id == tray.check_update_id {
                 return update(app, Message::CheckUpdate);
             }
@@ -1,29 +1,31 @@
 use crate::config::ServerConfig;
 use crate::settings;
 use iced::widget::text_editor;
-use std::path::PathBuf;
 
 use super::atom_helper_config;
+use super::state::F11App;
 
-pub fn build_server_config(
-    host: &str,
-    port: &str,
-    dir: &PathBuf,
-    server_side_regex: &text_editor::Content,
-    enable_size_limit: bool,
-    max_size: &str,
-) -> Option<ServerConfig> {
-    let (host, port) = atom_helper_config::parse_host_port(host, port)?;
-    let server_side_path_regex = atom_helper_config::parse_server_side_path_regex(server_side_regex);
-    let max_server_side_file_size = atom_helper_config::parse_max_server_side_file_size(max_size);
+pub fn build_server_config(app: &F11App) -> Option<ServerConfig> {
+    let (host, port) = atom_helper_config::parse_host_port(&app.host, &app.port)?;
+    let server_side_path_regex = atom_helper_config::parse_server_side_path_regex(&app.server_side_regex);
+    let max_server_side_file_size = atom_helper_config::parse_max_server_side_file_size(&app.max_size);
 
     Some(ServerConfig {
         host,
         port,
-        dir: dir.clone(),
+        dir: app.dir.clone(),
         server_side_path_regex,
-        enable_server_side_file_size_limit: enable_size_limit,
+        enable_server_side_file_size_limit: app.enable_size_limit,
         max_server_side_file_size,
+        size_rules: atom_helper_config::parse_size_rules(&app.size_rules),
+        quota_bytes: atom_helper_config::parse_quota(&app.quota_bytes),
+        quota_files: atom_helper_config::parse_quota(&app.quota_files),
+        pair_url_template: atom_helper_config::parse_pair_url_template(&app.pair_url_template),
+        resume_grace_secs: atom_helper_config::parse_resume_grace_secs(&app.resume_grace),
+        takeover: app.takeover,
+        require_device_approval: app.require_device_approval,
+        trusted_devices: app.trusted_devices.clone(),
+        device_rules: app.device_rules.clone(),
     })
 }
 
@@ -37,7 +39,7 @@ pub fn build_app_config(
 ) -> settings::AppConfig {
     let server_side_path_regex = atom_helper_config::parse_server_side_path_regex(server_side_regex);
     settings::AppConfig {
-        schema_version: 1,
+        schema_version: settings::SCHEMA_VERSION,
         server: settings::ServerConfigFile {
             host: server_cfg.host,
             port: server_cfg.port,
@@ -45,6 +47,15 @@ pub fn build_app_config(
             server_side_path_regex,
             enable_file_size_limit: server_cfg.enable_server_side_file_size_limit,
             max_file_size: server_cfg.max_server_side_file_size,
+            size_rules: server_cfg.size_rules.clone(),
+            quota_bytes: server_cfg.quota_bytes,
+            quota_files: server_cfg.quota_files,
+            pair_url_template: server_cfg.pair_url_template.clone(),
+            resume_grace_secs: server_cfg.resume_grace_secs,
+            takeover: server_cfg.takeover,
+            require_device_approval: server_cfg.require_device_approval,
+            trusted_devices: server_cfg.trusted_devices.clone(),
+            device_rules: server_cfg.device_rules.clone(),
         },
         ui: settings::UiConfig {
             follow_system_theme,
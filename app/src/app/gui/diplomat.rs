@@ -22,6 +22,14 @@ pub fn save_settings(app_cfg: &crate::settings::AppConfig) -> anyhow::Result<()>
     Ok(())
 }
 
+pub fn load_settings() -> anyhow::Result<Option<crate::settings::AppConfig>> {
+    crate::settings::load()
+}
+
+pub fn switch_profile(name: &str) -> anyhow::Result<()> {
+    crate::settings::switch_profile(name)
+}
+
 pub fn find_asset(name: &str) -> Option<PathBuf> {
     let name = name.trim_start_matches(['/', '\\']);
 
@@ -96,6 +104,8 @@ pub struct TrayHandle {
     pub _tray: tray_icon::TrayIcon,
     pub toggle_id: String,
     pub start_stop_id: String,
+    pub push_all_id: String,
+    pub request_upload_id: String,
     pub check_update_id: String,
     pub download_update_id: String,
     pub quit_id: String,
@@ -110,12 +120,16 @@ pub fn init_tray() -> Option<TrayHandle> {
     let menu = Menu::new();
     let toggle = MenuItem::new("最小化/恢复", true, None);
     let start_stop = MenuItem::new("启动/停止", true, None);
+    let push_all = MenuItem::new("推送全部到手机", true, None);
+    let request_upload = MenuItem::new("请求手机上传全部", true, None);
     let check_update = MenuItem::new("检查更新", true, None);
     let download_update = MenuItem::new("下载更新", true, None);
     let quit = MenuItem::new("退出", true, None);
 
     let _ = menu.append(&toggle);
     let _ = menu.append(&start_stop);
+    let _ = menu.append(&push_all);
+    let _ = menu.append(&request_upload);
     let _ = menu.append(&check_update);
     let _ = menu.append(&download_update);
     let _ = menu.append(&quit);
@@ -132,6 +146,8 @@ pub fn init_tray() -> Option<TrayHandle> {
         _tray: tray,
         toggle_id: toggle.id().0.clone(),
         start_stop_id: start_stop.id().0.clone(),
+        push_all_id: push_all.id().0.clone(),
+        request_upload_id: request_upload.id().0.clone(),
         check_update_id: check_update.id().0.clone(),
         download_update_id: download_update.id().0.clone(),
         quit_id: quit.id().0.clone(),
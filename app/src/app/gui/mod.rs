@@ -39,6 +39,9 @@ pub struct GuiFlags {
     pub server: ServerConfig,
     pub ui: settings::UiConfig,
     pub needs_dir_pick: bool,
+    pub load_error: Option<String>,
+    /// 通过 `--config` 指定了配置文件时禁用 profile 切换
+    pub profile_locked: bool,
 }
 
     fn preferred_font() -> Font {
@@ -96,14 +99,45 @@ pub struct GuiFlags {
                 running: false,
                 stopping: false,
                 connected: false,
+                client_device: None,
+                pending_takeover: None,
+                pending_device: None,
+                sync_progress: None,
+                tree_size: None,
                 last_error: String::new(),
                 show_advanced: flags.ui.show_advanced,
                 dark_mode: flags.ui.dark_mode,
                 manual_dark_mode: flags.ui.dark_mode,
                 follow_system_theme: flags.ui.follow_system_theme,
+                regex_issues: Vec::new(),
                 server_side_regex: regex,
                 enable_size_limit: flags.server.enable_server_side_file_size_limit,
                 max_size: flags.server.max_server_side_file_size.to_string(),
+                size_rules: text_editor::Content::with_text(
+                    &flags.server.size_rules.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"),
+                ),
+                quota_bytes: flags.server.quota_bytes.map(|v| v.to_string()).unwrap_or_default(),
+                quota_files: flags.server.quota_files.map(|v| v.to_string()).unwrap_or_default(),
+                pair_url_template: flags.server.pair_url_template.unwrap_or_default(),
+                resume_grace: flags.server.resume_grace_secs.to_string(),
+                takeover: flags.server.takeover,
+                require_device_approval: flags.server.require_device_approval,
+                trusted_devices: flags.server.trusted_devices,
+                device_rules: flags.server.device_rules,
+                rules_device: None,
+                rules_max_size: String::new(),
+                rules_regex: text_editor::Content::new(),
+                path_test_input: String::new(),
+                path_test_results: Vec::new(),
+                dry_running: false,
+                client_config: None,
+                profiles: settings::list_profiles(),
+                active_profile: settings::active_profile(),
+                new_profile_name: String::new(),
+                profile_locked: flags.profile_locked,
+                portable: settings::is_portable(),
+                lan_urls: Vec::new(),
+                pair_qr: None,
                 logs: Vec::new(),
                 logs_dirty: false,
                 follow_logs: flags.ui.follow_logs,
@@ -131,13 +165,25 @@ pub struct GuiFlags {
                 app.dir_input = path_helper::display_path(&app.dir);
             }
             actions::push_log(&mut app, "[info] F11eSync GUI 已启动".to_string());
+            actions::refresh_regex_issues(&mut app);
+            for issue in app.regex_issues.clone() {
+                actions::push_log(&mut app, format!("[warn] 过滤规则不合法: {issue}"));
+            }
+            if let Some(err) = flags.load_error {
+                app.last_error = format!("配置文件无法读取：{err}");
+                actions::push_log(&mut app, format!("[error] {err}"));
+            }
             (app, Command::none())
         }
 
         fn title(&self, window: iced::window::Id) -> String {
             if window == iced::window::Id::MAIN {
                 let s = if self.running { "运行中" } else { "已停止" };
-                format!("F11eSync ({s})")
+                if self.active_profile == settings::DEFAULT_PROFILE {
+                    format!("F11eSync ({s})")
+                } else {
+                    format!("F11eSync [{}] ({s})", self.active_profile)
+                }
             } else {
                 "F11eSync · 日志".to_string()
             }
@@ -1,5 +1,8 @@
-use crate::server::UiEvent;
-use iced::widget::text_editor;
+use crate::config::{DeviceRules, KnownDevice, ServerConfig, TakeoverPolicy};
+use std::collections::BTreeMap;
+use crate::proto::ClientConfig;
+use crate::server::{ServerCommand, UiEvent};
+use iced::widget::{qr_code, text_editor};
 use std::path::PathBuf;
 use std::time::{Duration as StdDuration, Instant};
 use tokio::sync::{mpsc, oneshot};
@@ -16,13 +19,38 @@ pub(super) enum Message {
     HostChanged(String),
     PortChanged(String),
     StartStop,
+    PushAll,
+    RequestUpload,
     CollapseLogsSidebar,
     ToggleAdvanced(bool),
     ToggleFollowSystemTheme(bool),
     ToggleDarkMode(bool),
     ServerRegexEdited(text_editor::Action),
+    SizeRulesEdited(text_editor::Action),
     EnableSizeLimit(bool),
     MaxSizeChanged(String),
+    QuotaBytesChanged(String),
+    QuotaFilesChanged(String),
+    PairUrlTemplateChanged(String),
+    ResumeGraceChanged(String),
+    TakeoverPolicySelected(TakeoverPolicy),
+    DisconnectClient,
+    ResolveTakeover(bool),
+    ToggleRequireDeviceApproval(bool),
+    ResolveDevice { accept: bool, remember: bool },
+    ForgetDevice(String),
+    DeviceRulesSelected(String),
+    DeviceRulesSizeLimit(bool),
+    DeviceRulesMaxSizeChanged(String),
+    DeviceRulesRegexEdited(text_editor::Action),
+    ForgetDeviceRules,
+    PathTestInputChanged(String),
+    TestPath,
+    DryRunTree,
+    DryRunDone(Vec<String>),
+    ProfileSelected(String),
+    NewProfileNameChanged(String),
+    CreateProfile,
     CheckUpdate,
     CheckUpdateDone(String),
     DownloadUpdate,
@@ -36,6 +64,7 @@ pub(super) enum Message {
 
 pub(super) struct BgServer {
     pub(super) shutdown: Option<oneshot::Sender<()>>,
+    pub(super) commands: mpsc::UnboundedSender<ServerCommand>,
 }
 
 impl BgServer {
@@ -44,6 +73,30 @@ impl BgServer {
             let _ = tx.send(());
         }
     }
+
+    pub(super) fn reconfigure(&self, cfg: ServerConfig) {
+        let _ = self.commands.send(ServerCommand::Reconfigure(cfg));
+    }
+
+    pub(super) fn push_all(&self) {
+        let _ = self.commands.send(ServerCommand::Push(Vec::new()));
+    }
+
+    pub(super) fn request_upload(&self) {
+        let _ = self.commands.send(ServerCommand::RequestUpload);
+    }
+
+    pub(super) fn disconnect_client(&self) {
+        let _ = self.commands.send(ServerCommand::DisconnectClient);
+    }
+
+    pub(super) fn resolve_takeover(&self, accept: bool) {
+        let _ = self.commands.send(ServerCommand::ResolveTakeover(accept));
+    }
+
+    pub(super) fn resolve_device(&self, accept: bool, remember: bool) {
+        let _ = self.commands.send(ServerCommand::ResolveDevice { accept, remember });
+    }
 }
 
 pub(super) struct F11App {
@@ -54,6 +107,15 @@ pub(super) struct F11App {
     pub(super) running: bool,
     pub(super) stopping: bool,
     pub(super) connected: bool,
+    /// 已连接客户端的设备名
+    pub(super) client_device: Option<String>,
+    /// 询问模式下等待确认的新设备
+    pub(super) pending_takeover: Option<String>,
+    /// 开启设备确认时等待确认的未知设备
+    pub(super) pending_device: Option<String>,
+    pub(super) sync_progress: Option<(u32, u32)>,
+    /// 同步目录中会发送的文件数、总字节数，以及是否超出配额
+    pub(super) tree_size: Option<(u64, u64, bool)>,
     pub(super) last_error: String,
 
     pub(super) show_advanced: bool,
@@ -62,8 +124,36 @@ pub(super) struct F11App {
     pub(super) follow_system_theme: bool,
 
     pub(super) server_side_regex: text_editor::Content,
+    pub(super) regex_issues: Vec<String>,
     pub(super) enable_size_limit: bool,
     pub(super) max_size: String,
+    pub(super) size_rules: text_editor::Content,
+    pub(super) quota_bytes: String,
+    pub(super) quota_files: String,
+    pub(super) pair_url_template: String,
+    pub(super) resume_grace: String,
+    pub(super) takeover: TakeoverPolicy,
+    pub(super) require_device_approval: bool,
+    pub(super) trusted_devices: Vec<KnownDevice>,
+    pub(super) device_rules: BTreeMap<String, DeviceRules>,
+    /// 正在查看/编辑规则的设备 id
+    pub(super) rules_device: Option<String>,
+    pub(super) rules_max_size: String,
+    pub(super) rules_regex: text_editor::Content,
+
+    pub(super) path_test_input: String,
+    pub(super) path_test_results: Vec<String>,
+    pub(super) dry_running: bool,
+    pub(super) client_config: Option<ClientConfig>,
+
+    pub(super) profiles: Vec<String>,
+    pub(super) active_profile: String,
+    pub(super) new_profile_name: String,
+    pub(super) profile_locked: bool,
+    pub(super) portable: bool,
+
+    pub(super) lan_urls: Vec<String>,
+    pub(super) pair_qr: Option<qr_code::Data>,
 
     pub(super) logs: Vec<String>,
     pub(super) logs_dirty: bool,
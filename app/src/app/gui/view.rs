@@ -1,9 +1,13 @@
 use iced::widget::{
-    button, checkbox, column, container, row, scrollable, text, text_editor, text_input,
+    button, checkbox, column, container, pick_list, qr_code, row, scrollable, text, text_editor,
+    text_input,
 };
 use iced::{alignment::Horizontal, Border, Element, Length, Pixels, Shadow, Theme};
 
 use super::atoms::antd;
+use crate::config::{KnownDevice, TakeoverPolicy};
+use super::atom_helper_config;
+use crate::server::atoms::atom_helper_limits;
 use super::molecules;
 use super::{AntCheckbox, AntSecondaryButton, AntTextButton, AntTextEditor, AntTextInput, F11App, Message};
 
@@ -100,6 +104,20 @@ pub fn main_window(app: &F11App) -> Element<'_, Message> {
     } else {
         "已停止"
     };
+    let mut status_text = status_text.to_string();
+    if let Some((done, total)) = app.sync_progress {
+        status_text = format!("{status_text} · 推送中 {done}/{total}");
+    }
+    if let Some((files, bytes, over_quota)) = app.tree_size {
+        let size = atom_helper_limits::human_bytes(bytes);
+        status_text = format!("{status_text} · 目录 {files} 个文件 / {size}");
+        if over_quota {
+            status_text = format!("{status_text}（超出配额）");
+        }
+    }
+    if app.portable {
+        status_text = format!("{status_text} · 便携模式");
+    }
 
     let header = row![
         column![
@@ -143,6 +161,18 @@ pub fn main_window(app: &F11App) -> Element<'_, Message> {
     .spacing(8)
     .align_items(iced::Alignment::Center);
 
+    let profile_row = row![
+        text("配置").width(Length::Fixed(LABEL_W)),
+        pick_list(
+            app.profiles.as_slice(),
+            Some(app.active_profile.clone()),
+            Message::ProfileSelected,
+        )
+        .width(Length::Fill),
+    ]
+    .spacing(8)
+    .align_items(iced::Alignment::Center);
+
     let host_row = row![
         text("Host").width(Length::Fixed(LABEL_W)),
         text_input("0.0.0.0", &app.host)
@@ -200,16 +230,99 @@ pub fn main_window(app: &F11App) -> Element<'_, Message> {
         Element::from(text(app.last_error.clone()).style(iced::theme::Text::Color(antd::ERROR)))
     };
 
+    let mut basic_content = column![].spacing(10).width(Length::Fill);
+    if !app.profile_locked {
+        basic_content = basic_content.push(profile_row);
+    }
     let basic = molecules::card(
         dark,
         "连接与目录",
-        column![dir_row, host_row, port_row]
-            .spacing(10)
-            .width(Length::Fill)
-            .into(),
+        basic_content.push(dir_row).push(host_row).push(port_row).into(),
     );
 
-    let actions = molecules::card(dark, "操作", column![controls, err].spacing(8).width(Length::Fill).into());
+    let transfer_controls = row![
+        button(text("推送全部到手机").width(Length::Fill).horizontal_alignment(Horizontal::Center))
+            .padding(10)
+            .width(Length::Fill)
+            .style(iced::theme::Button::custom(AntSecondaryButton))
+            .on_press_maybe((app.connected && app.sync_progress.is_none()).then_some(Message::PushAll)),
+        button(text("请求手机上传全部").width(Length::Fill).horizontal_alignment(Horizontal::Center))
+            .padding(10)
+            .width(Length::Fill)
+            .style(iced::theme::Button::custom(AntSecondaryButton))
+            .on_press_maybe(app.connected.then_some(Message::RequestUpload)),
+    ]
+    .spacing(8)
+    .width(Length::Fill);
+
+    let mut action_content = column![controls, transfer_controls].spacing(8).width(Length::Fill);
+    if let Some(device) = &app.client_device {
+        action_content = action_content.push(
+            row![
+                text(format!("已连接：{device}"))
+                    .size(12)
+                    .style(iced::theme::Text::Color(antd::subtext_color(dark)))
+                    .width(Length::Fill),
+                button(text("断开客户端"))
+                    .style(iced::theme::Button::custom(AntSecondaryButton))
+                    .on_press(Message::DisconnectClient),
+            ]
+            .spacing(8)
+            .align_items(iced::Alignment::Center),
+        );
+    }
+    if let Some(device) = &app.pending_takeover {
+        action_content = action_content.push(
+            row![
+                text(format!("新设备请求连接：{device}")).size(12).width(Length::Fill),
+                button(text("允许（替换当前连接）"))
+                    .style(iced::theme::Button::custom(AntSecondaryButton))
+                    .on_press(Message::ResolveTakeover(true)),
+                button(text("拒绝"))
+                    .style(iced::theme::Button::custom(AntSecondaryButton))
+                    .on_press(Message::ResolveTakeover(false)),
+            ]
+            .spacing(8)
+            .align_items(iced::Alignment::Center),
+        );
+    }
+    if let Some(device) = &app.pending_device {
+        action_content = action_content.push(
+            row![
+                text(format!("未知设备请求连接：{device}")).size(12).width(Length::Fill),
+                button(text("允许一次"))
+                    .style(iced::theme::Button::custom(AntSecondaryButton))
+                    .on_press(Message::ResolveDevice { accept: true, remember: false }),
+                button(text("信任此设备"))
+                    .style(iced::theme::Button::custom(AntSecondaryButton))
+                    .on_press(Message::ResolveDevice { accept: true, remember: true }),
+                button(text("拒绝"))
+                    .style(iced::theme::Button::custom(AntSecondaryButton))
+                    .on_press(Message::ResolveDevice { accept: false, remember: false }),
+            ]
+            .spacing(8)
+            .align_items(iced::Alignment::Center),
+        );
+    }
+    let actions = molecules::card(dark, "操作", action_content.push(err).into());
+
+    let pairing = app.pair_qr.as_ref().map(|data| {
+        let urls = app.lan_urls.iter().fold(column![].spacing(4), |col, url| {
+            col.push(text(url.clone()).size(12))
+        });
+        let content = column![
+            container(qr_code(data).cell_size(4))
+                .width(Length::Fill)
+                .center_x(),
+            text("在 Scripting 中填写以下任一地址，或直接扫码：")
+                .size(11)
+                .style(iced::theme::Text::Color(antd::subtext_color(dark))),
+            urls,
+        ]
+        .spacing(8)
+        .width(Length::Fill);
+        molecules::card(dark, "扫码连接", content.into())
+    });
 
     let advanced = if app.show_advanced {
         let size_limit_toggle = row![checkbox("启用服务端文件大小限制", app.enable_size_limit)
@@ -234,6 +347,137 @@ pub fn main_window(app: &F11App) -> Element<'_, Message> {
             None
         };
 
+        let size_rules = column![
+            text("大小规则（每行 模式=最大字节数，按顺序取第一条匹配，如 `.ts=2097152`、`images/**=5242880`）：")
+                .size(12)
+                .style(iced::theme::Text::Color(antd::subtext_color(dark))),
+            text_editor(&app.size_rules)
+                .on_action(Message::SizeRulesEdited)
+                .style(iced::theme::TextEditor::Custom(Box::new(AntTextEditor)))
+                .height(Length::Fixed(80.0)),
+        ]
+        .extend(atom_helper_config::size_rule_issues(&app.size_rules).into_iter().map(|issue| {
+            text(issue)
+                .size(11)
+                .style(iced::theme::Text::Color(antd::ERROR))
+                .into()
+        }))
+        .spacing(8);
+
+        let quota_rows = column![
+            row![
+                text("配额(字节)").width(Length::Fixed(80.0)),
+                text_input("不限制", &app.quota_bytes)
+                    .on_input(Message::QuotaBytesChanged)
+                    .style(iced::theme::TextInput::Custom(Box::new(AntTextInput)))
+                    .width(Length::Fill),
+            ]
+            .spacing(8)
+            .align_items(iced::Alignment::Center),
+            row![
+                text("配额(文件数)").width(Length::Fixed(80.0)),
+                text_input("不限制", &app.quota_files)
+                    .on_input(Message::QuotaFilesChanged)
+                    .style(iced::theme::TextInput::Custom(Box::new(AntTextInput)))
+                    .width(Length::Fill),
+            ]
+            .spacing(8)
+            .align_items(iced::Alignment::Center),
+        ]
+        .spacing(10);
+
+        let resume_grace_row = row![
+            text("断线保留(秒)").width(Length::Fixed(80.0)),
+            text_input("60", &app.resume_grace)
+                .on_input(Message::ResumeGraceChanged)
+                .style(iced::theme::TextInput::Custom(Box::new(AntTextInput)))
+                .width(Length::Fill),
+        ]
+        .spacing(8)
+        .align_items(iced::Alignment::Center);
+
+        let takeover_row = row![
+            text("新连接").width(Length::Fixed(80.0)),
+            pick_list(
+                [TakeoverPolicy::Reject, TakeoverPolicy::ReplaceOldest, TakeoverPolicy::Ask],
+                Some(app.takeover),
+                Message::TakeoverPolicySelected,
+            )
+            .width(Length::Fill),
+        ]
+        .spacing(8)
+        .align_items(iced::Alignment::Center);
+
+        let trusted_devices = app.trusted_devices.iter().fold(
+            column![checkbox("只自动接受已信任的设备", app.require_device_approval)
+                .on_toggle(Message::ToggleRequireDeviceApproval)
+                .style(iced::theme::Checkbox::Custom(Box::new(AntCheckbox)))]
+            .spacing(8),
+            |col, device| {
+                col.push(
+                    row![
+                        text(device.to_string()).size(12).width(Length::Fill),
+                        button(text("移除"))
+                            .style(iced::theme::Button::custom(AntSecondaryButton))
+                            .on_press(Message::ForgetDevice(device.id.clone())),
+                    ]
+                    .spacing(8)
+                    .align_items(iced::Alignment::Center),
+                )
+            },
+        );
+
+        let device_options: Vec<KnownDevice> = app
+            .device_rules
+            .iter()
+            .map(|(id, rules)| KnownDevice { id: id.clone(), name: rules.name.clone() })
+            .collect();
+        let selected_device = app
+            .rules_device
+            .as_ref()
+            .and_then(|id| device_options.iter().find(|d| &d.id == id).cloned());
+        let mut device_rules = column![
+            text("设备规则（各设备最近一次上报的客户端规则，设备握手时立即生效）：")
+                .size(12)
+                .style(iced::theme::Text::Color(antd::subtext_color(dark))),
+            row![
+                pick_list(device_options, selected_device, |d| Message::DeviceRulesSelected(d.id))
+                    .placeholder("选择设备")
+                    .width(Length::Fill),
+                button(text("删除"))
+                    .style(iced::theme::Button::custom(AntSecondaryButton))
+                    .on_press_maybe(app.rules_device.is_some().then_some(Message::ForgetDeviceRules)),
+            ]
+            .spacing(8)
+            .align_items(iced::Alignment::Center),
+        ]
+        .spacing(8);
+        if let Some(rules) = app.rules_device.as_ref().and_then(|id| app.device_rules.get(id)) {
+            device_rules = device_rules
+                .push(
+                    checkbox("启用客户端文件大小限制", rules.enable_file_size_limit.unwrap_or(false))
+                        .on_toggle(Message::DeviceRulesSizeLimit)
+                        .style(iced::theme::Checkbox::Custom(Box::new(AntCheckbox))),
+                )
+                .push(
+                    row![
+                        text("最大(字节)").width(Length::Fixed(80.0)),
+                        text_input("未声明", &app.rules_max_size)
+                            .on_input(Message::DeviceRulesMaxSizeChanged)
+                            .style(iced::theme::TextInput::Custom(Box::new(AntTextInput)))
+                            .width(Length::Fill),
+                    ]
+                    .spacing(8)
+                    .align_items(iced::Alignment::Center),
+                )
+                .push(
+                    text_editor(&app.rules_regex)
+                        .on_action(Message::DeviceRulesRegexEdited)
+                        .style(iced::theme::TextEditor::Custom(Box::new(AntTextEditor)))
+                        .height(Length::Fixed(80.0)),
+                );
+        }
+
         let regex_editor = column![
             text("过滤规则(每行一个正则)：")
                 .size(12)
@@ -246,13 +490,92 @@ pub fn main_window(app: &F11App) -> Element<'_, Message> {
                 .style(iced::theme::TextEditor::Custom(Box::new(AntTextEditor)))
                 .height(Length::Fixed(120.0)),
         ]
+        .extend(app.regex_issues.iter().map(|issue| {
+            text(issue.clone())
+                .size(11)
+                .style(iced::theme::Text::Color(antd::ERROR))
+                .into()
+        }))
+        .spacing(8);
+
+        let pair_url_row = column![
+            text("二维码模板（可选，{url} 替换为服务地址）：")
+                .size(12)
+                .style(iced::theme::Text::Color(antd::subtext_color(dark))),
+            text_input("scripting://run/F11eSync?serverUrl={url}", &app.pair_url_template)
+                .on_input(Message::PairUrlTemplateChanged)
+                .style(iced::theme::TextInput::Custom(Box::new(AntTextInput)))
+                .width(Length::Fill),
+        ]
         .spacing(8);
 
+        let new_profile_row = row![
+            text_input("新配置名称", &app.new_profile_name)
+                .on_input(Message::NewProfileNameChanged)
+                .on_submit(Message::CreateProfile)
+                .style(iced::theme::TextInput::Custom(Box::new(AntTextInput)))
+                .width(Length::Fill),
+            button("新建配置")
+                .style(iced::theme::Button::custom(AntSecondaryButton))
+                .on_press_maybe(
+                    (!actions_disabled && !app.new_profile_name.trim().is_empty())
+                        .then_some(Message::CreateProfile),
+                ),
+        ]
+        .spacing(8)
+        .align_items(iced::Alignment::Center);
+
+        let client_hint = if app.client_config.is_some() {
+            "已包含当前客户端规则与大小限制"
+        } else {
+            "客户端未连接，仅测试服务端规则"
+        };
+        let path_test = app.path_test_results.iter().fold(
+            column![
+                text("规则测试（同步目录下的相对路径）：")
+                    .size(12)
+                    .style(iced::theme::Text::Color(antd::subtext_color(dark))),
+                text(client_hint)
+                    .size(11)
+                    .style(iced::theme::Text::Color(antd::subtext_color(dark))),
+                row![
+                    text_input("scripts/main.tsx", &app.path_test_input)
+                        .on_input(Message::PathTestInputChanged)
+                        .on_submit(Message::TestPath)
+                        .style(iced::theme::TextInput::Custom(Box::new(AntTextInput)))
+                        .width(Length::Fill),
+                    button("测试")
+                        .style(iced::theme::Button::custom(AntSecondaryButton))
+                        .on_press_maybe((!app.path_test_input.trim().is_empty()).then_some(Message::TestPath)),
+                    button("预演全部")
+                        .style(iced::theme::Button::custom(AntSecondaryButton))
+                        .on_press_maybe((!app.dry_running).then_some(Message::DryRunTree)),
+                ]
+                .spacing(8)
+                .align_items(iced::Alignment::Center),
+            ]
+            .spacing(8),
+            |col, line| col.push(text(line.clone()).size(11)),
+        );
+
         let mut adv_content = column![size_limit_toggle].spacing(10).width(Length::Fill);
         if let Some(v) = size_limit_value {
             adv_content = adv_content.push(v);
         }
+        adv_content = adv_content.push(size_rules);
+        adv_content = adv_content.push(quota_rows);
+        adv_content = adv_content.push(resume_grace_row);
+        adv_content = adv_content.push(takeover_row);
+        adv_content = adv_content.push(trusted_devices);
+        if !app.device_rules.is_empty() {
+            adv_content = adv_content.push(device_rules);
+        }
         adv_content = adv_content.push(regex_editor);
+        adv_content = adv_content.push(path_test);
+        adv_content = adv_content.push(pair_url_row);
+        if !app.profile_locked {
+            adv_content = adv_content.push(new_profile_row);
+        }
 
         Some(molecules::card(dark, "高级设置", adv_content.into()))
     } else {
@@ -260,6 +583,9 @@ pub fn main_window(app: &F11App) -> Element<'_, Message> {
     };
 
     let mut col = column![header, basic, actions].spacing(10).padding(10);
+    if let Some(pairing) = pairing {
+        col = col.push(pairing);
+    }
     if let Some(advanced) = advanced {
         col = col.push(advanced);
     }
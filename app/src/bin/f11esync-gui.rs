@@ -5,8 +5,11 @@ compile_error!("f11esync-gui 需要启用 gui feature（例如 `cargo build --bi
 
 #[path = "../app/mod.rs"]
 mod app;
+// 与 CLI 入口共用源码；只在 CLI 中使用的条目在定义处以 `cfg_attr(feature = "gui", allow(dead_code))` 放行
 #[path = "../config.rs"]
 mod config;
+#[path = "../logging.rs"]
+mod logging;
 #[path = "../proto.rs"]
 mod proto;
 #[path = "../server/mod.rs"]
@@ -23,23 +26,20 @@ use clap::{CommandFactory, FromArgMatches};
 use config::Cli;
 
 fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "info,tower_http=warn".into()),
-        )
-        .init();
-
     let matches = Cli::command().get_matches();
     let cli = Cli::from_arg_matches(&matches)?;
+    logging::init(&cli)?;
+    settings::init_config_path(cli.config_path.as_deref(), cli.profile.as_deref())?;
 
-    let file = settings::load().unwrap_or(None);
-    let needs_dir_pick = file.is_none();
+    let (file, load_error) = settings::load_or_backup_invalid();
+    let needs_dir_pick = file.is_none() && load_error.is_none();
     let effective = settings::merge(&cli, &matches, file);
     app::run_gui(app::GuiFlags {
         server: effective.server,
         ui: effective.ui,
         needs_dir_pick,
+        load_error,
+        profile_locked: cli.config_path.is_some(),
     })?;
     Ok(())
 }
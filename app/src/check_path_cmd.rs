@@ -0,0 +1,79 @@
+use crate::config::{Cli, ClientSim, Command};
+use crate::proto::ClientConfig;
+use crate::server::molecules::path_check::PathChecker;
+use crate::settings;
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use std::path::PathBuf;
+
+fn build_checker(cli: &Cli, matches: &ArgMatches, sim: &ClientSim) -> Result<(PathChecker, PathBuf)> {
+    let file = settings::load()?;
+    let effective = settings::merge(cli, matches, file);
+    for issue in settings::regex_issues(&effective.server.server_side_path_regex) {
+        eprintln!("[warn] {issue}（已忽略）");
+    }
+
+    let client = ClientConfig {
+        pathRegex: (!sim.client_regex.is_empty()).then(|| sim.client_regex.clone()),
+        enableFileSizeLimit: sim.client_max_size.map(|_| true),
+        maxFileSize: sim.client_max_size,
+        encodings: None,
+        compression: None,
+    };
+    let dir = effective.server.dir.clone();
+    Ok((PathChecker::new(effective.server, client), dir))
+}
+
+pub fn run(cli: &Cli, matches: &ArgMatches) -> Result<()> {
+    let Some(Command::CheckPath { paths, all, client }) = &cli.command else {
+        return Ok(());
+    };
+    let (checker, dir) = build_checker(cli, matches, client)?;
+
+    for rel in paths {
+        let report = checker.check_rel(rel);
+        let kind = if report.is_dir { "目录" } else { "文件" };
+        println!("{rel} [{kind}] {}", report.verdict.describe());
+    }
+
+    if *all {
+        let skipped = checker.dry_run(&dir);
+        for report in &skipped {
+            let suffix = if report.is_dir { "/" } else { "" };
+            println!("{}{suffix} {}", report.rel, report.verdict.describe());
+        }
+        println!("预演完成（{}）：{} 项会被跳过", dir.display(), skipped.len());
+    }
+    Ok(())
+}
+
+pub fn run_preview(cli: &Cli, matches: &ArgMatches) -> Result<()> {
+    let Some(Command::SyncPreview { json, list, client }) = &cli.command else {
+        return Ok(());
+    };
+    let (checker, dir) = build_checker(cli, matches, client)?;
+    let preview = checker.preview(&dir);
+
+    if *json {
+        println!("{}", serde_json::to_string_pretty(&preview).context("序列化报告失败")?);
+        return Ok(());
+    }
+
+    if *list {
+        for entry in &preview.entries {
+            if entry.is_dir {
+                println!("{}/", entry.rel);
+            } else if entry.chunks > 0 {
+                println!("{} {}B（{} 个分片）", entry.rel, entry.bytes, entry.chunks);
+            } else {
+                println!("{} {}B", entry.rel, entry.bytes);
+            }
+        }
+    }
+    for report in &preview.skipped {
+        let suffix = if report.is_dir { "/" } else { "" };
+        println!("{}{suffix} {}", report.rel, report.verdict.describe());
+    }
+    println!("{}（{}）", preview.summary(), dir.display());
+    Ok(())
+}
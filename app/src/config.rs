@@ -1,4 +1,7 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use crate::proto::ClientConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::net::IpAddr;
 use std::path::PathBuf;
 
@@ -10,30 +13,222 @@ pub struct ServerConfig {
     pub server_side_path_regex: Vec<String>,
     pub enable_server_side_file_size_limit: bool,
     pub max_server_side_file_size: u64,
+    /// 按路径/扩展名的大小规则，按顺序取第一条匹配的规则代替全局服务端限制
+    pub size_rules: Vec<SizeRule>,
+    /// 待同步内容的总字节数/文件数配额，`sync_all` 与新目录遍历前检查；`None` 表示不限制
+    pub quota_bytes: Option<u64>,
+    pub quota_files: Option<u64>,
+    pub pair_url_template: Option<String>,
+    /// 客户端断线后保留会话的秒数，期间用 `hello.sessionId` 重连可恢复；0 表示不保留
+    pub resume_grace_secs: u64,
+    pub takeover: TakeoverPolicy,
+    /// 开启后只有 `trusted_devices` 中的设备自动接受，其他设备需在 GUI/stdin 中确认
+    pub require_device_approval: bool,
+    pub trusted_devices: Vec<KnownDevice>,
+    /// 按 `deviceId` 记住的客户端规则，设备握手时立即生效
+    pub device_rules: BTreeMap<String, DeviceRules>,
+}
+
+/// 单条大小规则：`pattern` 为 glob（不含 `/` 时只匹配文件名，`**` 可跨目录）或扩展名（如 `.ts`）。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeRule {
+    pub pattern: String,
+    pub max_size: u64,
+}
+
+impl std::str::FromStr for SizeRule {
+    type Err = String;
+
+    /// 命令行/GUI 写法：`模式=最大字节数`，如 `*.ts=2097152`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, size) = s.rsplit_once('=').ok_or_else(|| format!("`{s}` 缺少 `=`，应为 模式=最大字节数"))?;
+        let pattern = pattern.trim();
+        if pattern.is_empty() {
+            return Err(format!("`{s}` 缺少匹配模式"));
+        }
+        let max_size = size.trim().parse().map_err(|_| format!("`{s}` 的大小不是整数字节数"))?;
+        Ok(Self { pattern: pattern.to_string(), max_size })
+    }
+}
+
+impl std::fmt::Display for SizeRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.pattern, self.max_size)
+    }
+}
+
+/// 客户端在 `hello` 中声明的设备身份（`deviceId` 由客户端首次运行时生成并持久化）。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownDevice {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+impl std::fmt::Display for KnownDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let short: String = self.id.chars().take(8).collect();
+        if self.name.is_empty() {
+            write!(f, "未命名设备 ({short})")
+        } else {
+            write!(f, "{} ({short})", self.name)
+        }
+    }
+}
+
+/// 某台设备最近一次 `configure` 中的过滤规则与大小限制（不含编码/压缩等能力声明）。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceRules {
+    pub name: String,
+    pub enable_file_size_limit: Option<bool>,
+    pub max_file_size: Option<u64>,
+    pub path_regex: Option<Vec<String>>,
+}
+
+impl DeviceRules {
+    pub fn from_client(name: &str, cfg: &ClientConfig) -> Self {
+        Self {
+            name: name.to_string(),
+            enable_file_size_limit: cfg.enableFileSizeLimit,
+            max_file_size: cfg.maxFileSize,
+            path_regex: cfg.pathRegex.clone(),
+        }
+    }
+
+    /// 覆盖 `cfg` 中的规则字段，编码/压缩保持不变。
+    pub fn apply_to(&self, cfg: &mut ClientConfig) {
+        cfg.enableFileSizeLimit = self.enable_file_size_limit;
+        cfg.maxFileSize = self.max_file_size;
+        cfg.pathRegex = self.path_regex.clone();
+    }
+}
+
+/// 已有客户端连接时，新连接的处理方式。
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TakeoverPolicy {
+    /// 拒绝新连接
+    #[default]
+    Reject,
+    /// 断开旧连接，接受新连接
+    ReplaceOldest,
+    /// 在 GUI 中询问（无界面时通过 stdin 输入 accept/reject）
+    Ask,
+}
+
+impl std::fmt::Display for TakeoverPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TakeoverPolicy::Reject => "拒绝新连接",
+            TakeoverPolicy::ReplaceOldest => "替换旧连接",
+            TakeoverPolicy::Ask => "询问",
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "gui", allow(dead_code))]
 pub enum RunMode {
     CheckUpdate,
     DownloadUpdate,
     CliServer,
+    Config,
+    CheckPath,
+    SyncPreview,
+    Push,
+    Pull,
     #[cfg_attr(not(feature = "gui"), allow(dead_code))]
     Gui,
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// 查看/修改配置文件
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// 测试相对路径是否会被同步（服务端/客户端规则、内置忽略、文件大小限制）
+    CheckPath {
+        /// 同步目录下的相对路径，可指定多个
+        #[arg(required_unless_present = "all")]
+        paths: Vec<String>,
+        /// 预演整个同步目录，列出所有会被跳过的条目
+        #[arg(long)]
+        all: bool,
+        #[command(flatten)]
+        client: ClientSim,
+    },
+    /// 让本机运行中的服务把全部文件（或指定子路径）推送到已连接的手机（通过 HTTP 接口）
+    Push {
+        /// 只推送这些子路径（相对同步目录，例如 widgets/weather），省略时推送全部
+        paths: Vec<String>,
+    },
+    /// 让本机运行中的服务请求手机上传全部文件（上传前会为同步目录拍快照）
+    Pull,
+    /// 预演“上传全部”：统计会发送的文件/目录/字节数/分片数以及每个跳过原因，不发送任何内容
+    SyncPreview {
+        /// 以 JSON 输出完整报告
+        #[arg(long)]
+        json: bool,
+        /// 逐条列出会发送的条目
+        #[arg(long)]
+        list: bool,
+        #[command(flatten)]
+        client: ClientSim,
+    },
+}
+
+/// 离线测试时模拟的客户端配置
+#[derive(Args, Debug, Clone, Default)]
+pub struct ClientSim {
+    /// 模拟客户端过滤规则（正则），可重复指定
+    #[arg(long = "client-regex")]
+    pub client_regex: Vec<String>,
+    /// 模拟客户端文件大小限制（字节）
+    #[arg(long)]
+    pub client_max_size: Option<u64>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigAction {
+    /// 输出配置文件路径
+    Path,
+    /// 输出生效配置（配置文件 + 环境变量 + 命令行参数）
+    Show,
+    /// 修改配置项，例如 `config set server.port 10081`
+    Set { key: String, value: String },
+    /// 校验配置（过滤正则、目录、端口）
+    Validate,
+    /// 列出配置 profile（* 为当前使用的）
+    Profiles,
+}
+
 #[derive(Parser, Debug, Clone)]
-#[command(name = "f11esync", version, about = "F11eSync Rust server (Socket.IO compatible)")]
+#[command(
+    name = "f11esync",
+    version,
+    about = "F11eSync Rust server (Socket.IO compatible)",
+    after_help = "配置优先级：命令行参数 > F11ESYNC_* 环境变量 > 配置文件 > 默认值"
+)]
 pub struct Cli {
     /// 端口号
-    #[arg(short = 'p', long, default_value_t = 10080)]
+    #[arg(short = 'p', long, env = "F11ESYNC_PORT", default_value_t = 10080)]
     pub port: u16,
 
     /// 主机地址
-    #[arg(short = 'H', long, default_value = "0.0.0.0")]
+    #[arg(short = 'H', long, env = "F11ESYNC_HOST", default_value = "0.0.0.0")]
     pub host: IpAddr,
 
     /// 监听目录（等价于原 Bun 版本的 -d）
-    #[arg(short = 'd', long)]
+    #[arg(short = 'd', long, env = "F11ESYNC_DIR")]
     pub dir: Option<PathBuf>,
 
     /// 检查更新（只输出结果后退出）
@@ -49,20 +244,87 @@ pub struct Cli {
     pub cli: bool,
 
     /// 服务端过滤规则（正则），可重复指定；默认会过滤 .DS_Store 与 __MACOSX
+    /// （环境变量 F11ESYNC_PATH_REGEX：每行一条，或 YAML 列表 `["a", "b"]`）
     #[arg(long = "path-regex")]
     pub path_regex: Vec<String>,
 
     /// 是否启用服务端文件大小限制（默认不启用；客户端也有自己的限制）
-    #[arg(long)]
+    #[arg(long, env = "F11ESYNC_ENABLE_FILE_SIZE_LIMIT")]
     pub enable_file_size_limit: bool,
 
     /// 服务端最大文件大小（字节）
-    #[arg(long, default_value_t = 250 * 1024)]
+    #[arg(long, env = "F11ESYNC_MAX_FILE_SIZE", default_value_t = 250 * 1024)]
     pub max_file_size: u64,
+
+    /// 按路径/扩展名的大小规则 `模式=最大字节数`，可重复指定，按顺序取第一条匹配的规则，
+    /// 例如 `--size-rule "images/**=5242880" --size-rule ".ts=2097152"`
+    /// （环境变量 F11ESYNC_SIZE_RULE：每行一条，或 YAML 列表 `["images/**=5242880", ".ts=2097152"]`）
+    #[arg(long = "size-rule")]
+    pub size_rule: Vec<SizeRule>,
+
+    /// 待同步内容的总字节数上限，超出时中止“上传全部”/新目录遍历（默认不限制）
+    #[arg(long = "quota-bytes", env = "F11ESYNC_QUOTA_BYTES")]
+    pub quota_bytes: Option<u64>,
+
+    /// 待同步文件数上限（默认不限制）
+    #[arg(long = "quota-files", env = "F11ESYNC_QUOTA_FILES")]
+    pub quota_files: Option<u64>,
+
+    /// 二维码内容模板，`{url}` 会替换为服务地址，例如 `scripting://run/F11eSync?serverUrl={url}`
+    #[arg(long = "pair-url", env = "F11ESYNC_PAIR_URL")]
+    pub pair_url: Option<String>,
+
+    /// 客户端断线后保留会话（配置、进行中的传输、离线期间的变更）的秒数，0 表示不保留
+    #[arg(long = "resume-grace", env = "F11ESYNC_RESUME_GRACE", default_value_t = 60)]
+    pub resume_grace: u64,
+
+    /// 已有客户端连接时如何处理新连接
+    #[arg(long, value_enum, env = "F11ESYNC_TAKEOVER", default_value_t = TakeoverPolicy::Reject)]
+    pub takeover: TakeoverPolicy,
+
+    /// 只自动接受已信任的设备，未知设备需在 GUI 中确认（无界面时输入 approve/trust/deny）
+    #[arg(long = "require-approval", env = "F11ESYNC_REQUIRE_APPROVAL")]
+    pub require_approval: bool,
+
+    /// 日志格式
+    #[arg(long, value_enum, env = "F11ESYNC_LOG_FORMAT", default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// 额外写入日志文件（按天、按大小轮转）
+    #[arg(long, env = "F11ESYNC_LOG_FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// 单个日志文件最大字节数，超过后轮转；0 表示只按天轮转
+    #[arg(long, env = "F11ESYNC_LOG_MAX_SIZE", default_value_t = 10 * 1024 * 1024)]
+    pub log_max_size: u64,
+
+    /// 保留的历史日志文件个数
+    #[arg(long, env = "F11ESYNC_LOG_KEEP", default_value_t = 7)]
+    pub log_keep: usize,
+
+    /// 使用指定的配置文件（读取与保存），可借此在同一台机器上运行多个互相隔离的实例
+    #[arg(long = "config", env = "F11ESYNC_CONFIG")]
+    pub config_path: Option<PathBuf>,
+
+    /// 使用指定名称的配置 profile（default 为默认配置）；便携模式下保存在可执行文件旁
+    #[arg(long, env = "F11ESYNC_PROFILE")]
+    pub profile: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
 }
 
 impl Cli {
+    #[cfg_attr(feature = "gui", allow(dead_code))]
     pub fn mode(&self) -> RunMode {
+        match self.command {
+            Some(Command::Config { .. }) => return RunMode::Config,
+            Some(Command::CheckPath { .. }) => return RunMode::CheckPath,
+            Some(Command::SyncPreview { .. }) => return RunMode::SyncPreview,
+            Some(Command::Push { .. }) => return RunMode::Push,
+            Some(Command::Pull) => return RunMode::Pull,
+            None => {}
+        }
         if self.update {
             return RunMode::CheckUpdate;
         }
@@ -0,0 +1,73 @@
+use crate::config::{Cli, Command, ConfigAction};
+use crate::settings;
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use std::net::{SocketAddr, TcpListener};
+
+pub fn run(cli: &Cli, matches: &ArgMatches) -> Result<()> {
+    let Some(Command::Config { action }) = &cli.command else {
+        return Ok(());
+    };
+
+    match action {
+        ConfigAction::Path => {
+            println!("{}", settings::config_file_path().display());
+            Ok(())
+        }
+        ConfigAction::Show => {
+            let file = settings::load()?;
+            let effective = settings::merge_file(cli, matches, file);
+            let yaml = serde_yaml::to_string(&effective).context("序列化配置失败")?;
+            print!("{yaml}");
+            Ok(())
+        }
+        ConfigAction::Set { key, value } => {
+            let current = settings::load()?.unwrap_or_default();
+            let next = settings::set_value(&current, key, value)?;
+            settings::save(&next)?;
+            println!("已更新 {key}（{}）", settings::config_file_path().display());
+            Ok(())
+        }
+        ConfigAction::Validate => validate(cli, matches),
+        ConfigAction::Profiles => {
+            let active = settings::active_profile();
+            for name in settings::list_profiles() {
+                let mark = if name == active { "*" } else { " " };
+                println!("{mark} {name}");
+            }
+            if settings::is_portable() {
+                println!("（便携模式: {}）", settings::config_base_dir().display());
+            }
+            Ok(())
+        }
+    }
+}
+
+fn validate(cli: &Cli, matches: &ArgMatches) -> Result<()> {
+    let path = settings::config_file_path();
+    let file = settings::load()?;
+    if file.is_none() {
+        println!("配置文件不存在，将使用默认配置: {}", path.display());
+    }
+    let effective = settings::merge_file(cli, matches, file);
+
+    let issues = settings::validate(&effective);
+    for issue in &issues {
+        println!("[error] {issue}");
+    }
+
+    let dir = &effective.server.dir;
+    if !dir.exists() {
+        println!("[warn] 同步目录不存在，启动时会自动创建: {}", dir.display());
+    }
+    let addr = SocketAddr::new(effective.server.host, effective.server.port);
+    if effective.server.port != 0 && TcpListener::bind(addr).is_err() {
+        println!("[warn] 端口当前不可用（可能已被占用）: {addr}");
+    }
+
+    if !issues.is_empty() {
+        anyhow::bail!("配置校验失败: {} 个问题", issues.len());
+    }
+    println!("配置校验通过: {}", path.display());
+    Ok(())
+}
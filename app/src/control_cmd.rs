@@ -0,0 +1,38 @@
+use crate::config::Cli;
+use crate::server::atoms::atom_helper_pairing;
+use crate::settings;
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+
+/// 通过 HTTP 接口控制本机运行中的服务（host/port 取自生效配置，口令取自 `control_token_path`）；`body` 以 JSON 发送。
+pub async fn post(cli: &Cli, matches: &ArgMatches, path: &str, body: Option<serde_json::Value>) -> Result<()> {
+    let file = settings::load()?;
+    let server = settings::merge(cli, matches, file).server;
+    let host = if server.host.is_unspecified() {
+        std::net::IpAddr::from([127, 0, 0, 1])
+    } else {
+        server.host
+    };
+    let url = format!("{}{path}", atom_helper_pairing::format_http_url(host, server.port));
+    let token_path = settings::control_token_path();
+    let token = std::fs::read_to_string(&token_path)
+        .with_context(|| format!("读取控制口令失败: {}（服务是否已用同一配置启动？）", token_path.display()))?;
+    let mut req = reqwest::Client::new()
+        .post(&url)
+        .bearer_auth(token.trim())
+        .timeout(std::time::Duration::from_secs(5));
+    if let Some(body) = body {
+        req = req.json(&body);
+    }
+    let resp = req
+        .send()
+        .await
+        .with_context(|| format!("无法连接到运行中的服务: {url}"))?;
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+    if !status.is_success() {
+        anyhow::bail!("请求失败（{status}）: {body}");
+    }
+    println!("{body}");
+    Ok(())
+}
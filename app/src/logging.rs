@@ -0,0 +1,185 @@
+use crate::config::{Cli, LogFormat};
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+pub fn init(cli: &Cli) -> Result<()> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| "info,tower_http=warn".into());
+
+    let mut layers: Vec<BoxedLayer> = vec![fmt_layer(cli.log_format, std::io::stderr, true)];
+    if let Some(path) = &cli.log_file {
+        let file = RotatingFile::open(path, cli.log_max_size, cli.log_keep)?;
+        layers.push(fmt_layer(cli.log_format, file, false));
+    }
+
+    tracing_subscriber::registry().with(layers).with(filter).init();
+    Ok(())
+}
+
+fn fmt_layer<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Text => layer.with_ansi(ansi).boxed(),
+        LogFormat::Json => layer.json().flatten_event(true).boxed(),
+    }
+}
+
+/// 日志文件：跨天或超过 `max_size` 时把当前文件改名为 `<name>.<YYYY-MM-DD>[.N]`，
+/// 并只保留最近 `keep` 个历史文件。
+#[derive(Clone)]
+pub struct RotatingFile {
+    inner: Arc<Mutex<RotatingInner>>,
+}
+
+struct RotatingInner {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    day: u64,
+    max_size: u64,
+    keep: usize,
+}
+
+pub struct RotatingWriter {
+    inner: Arc<Mutex<RotatingInner>>,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, max_size: u64, keep: usize) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("创建日志目录失败: {}", parent.display()))?;
+        }
+        let file = open_append(path).with_context(|| format!("打开日志文件失败: {}", path.display()))?;
+        let meta = file.metadata().ok();
+        let size = meta.as_ref().map(|m| m.len()).unwrap_or(0);
+        let day = meta
+            .and_then(|m| m.modified().ok())
+            .map(day_of)
+            .unwrap_or_else(|| day_of(SystemTime::now()));
+        Ok(Self {
+            inner: Arc::new(Mutex::new(RotatingInner {
+                path: path.to_path_buf(),
+                file,
+                size,
+                day,
+                max_size,
+                keep,
+            })),
+        })
+    }
+}
+
+impl<'a> MakeWriter<'a> for RotatingFile {
+    type Writer = RotatingWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RotatingWriter {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl Write for RotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).file.flush()
+    }
+}
+
+impl RotatingInner {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let today = day_of(SystemTime::now());
+        let too_big = self.max_size > 0 && self.size > 0 && self.size + buf.len() as u64 > self.max_size;
+        if today != self.day || too_big {
+            self.rotate(today)?;
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn rotate(&mut self, today: u64) -> std::io::Result<()> {
+        self.file.flush()?;
+        let stamp = format_day(self.day);
+        let mut target = suffixed(&self.path, &stamp);
+        let mut n = 1;
+        while target.exists() {
+            target = suffixed(&self.path, &format!("{stamp}.{n}"));
+            n += 1;
+        }
+        std::fs::rename(&self.path, &target)?;
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        self.day = today;
+        self.prune();
+        Ok(())
+    }
+
+    fn prune(&self) {
+        let (Some(dir), Some(name)) = (self.path.parent(), self.path.file_name()) else {
+            return;
+        };
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        let prefix = format!("{}.", name.to_string_lossy());
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let mut rotated = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+            .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+            .collect::<Vec<_>>();
+        if rotated.len() <= self.keep {
+            return;
+        }
+        rotated.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        for (_, path) in rotated.into_iter().skip(self.keep) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut s = path.as_os_str().to_os_string();
+    s.push(".");
+    s.push(suffix);
+    PathBuf::from(s)
+}
+
+fn day_of(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 86_400
+}
+
+/// UTC 日期（civil-from-days）
+fn format_day(days: u64) -> String {
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{y:04}-{m:02}-{d:02}")
+}
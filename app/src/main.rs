@@ -1,5 +1,9 @@
 mod app;
+mod check_path_cmd;
 mod config;
+mod config_cmd;
+mod control_cmd;
+mod logging;
 mod proto;
 mod server;
 mod settings;
@@ -11,15 +15,10 @@ use clap::{CommandFactory, FromArgMatches};
 use config::{Cli, RunMode};
 
 fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "info,tower_http=warn".into()),
-        )
-        .init();
-
     let matches = Cli::command().get_matches();
     let cli = Cli::from_arg_matches(&matches)?;
+    logging::init(&cli)?;
+    settings::init_config_path(cli.config_path.as_deref(), cli.profile.as_deref())?;
 
     match cli.mode() {
         RunMode::CheckUpdate => {
@@ -35,9 +34,29 @@ fn main() -> Result<()> {
             }
             Ok(())
         }
+        RunMode::Config => config_cmd::run(&cli, &matches),
+        RunMode::CheckPath => check_path_cmd::run(&cli, &matches),
+        RunMode::SyncPreview => check_path_cmd::run_preview(&cli, &matches),
+        RunMode::Push => {
+            let rt = tokio::runtime::Runtime::new()?;
+            let paths = match &cli.command {
+                Some(config::Command::Push { paths }) => paths.clone(),
+                _ => Vec::new(),
+            };
+            let body = (!paths.is_empty()).then(|| serde_json::json!({ "paths": paths }));
+            rt.block_on(control_cmd::post(&cli, &matches, "/api/sync/push", body))
+        }
+        RunMode::Pull => {
+            let rt = tokio::runtime::Runtime::new()?;
+            rt.block_on(control_cmd::post(&cli, &matches, "/api/upload/request", None))
+        }
         RunMode::CliServer => {
-            let file = settings::load().unwrap_or(None);
+            let file = settings::load()?;
             let effective = settings::merge(&cli, &matches, file);
+            let issues = settings::regex_issues(&effective.server.server_side_path_regex);
+            if !issues.is_empty() {
+                anyhow::bail!("过滤规则不合法:\n  {}", issues.join("\n  "));
+            }
             let rt = tokio::runtime::Runtime::new()?;
             rt.block_on(server::run_headless(effective.server))?;
             Ok(())
@@ -45,13 +64,15 @@ fn main() -> Result<()> {
         RunMode::Gui => {
             #[cfg(feature = "gui")]
             {
-                let file = settings::load().unwrap_or(None);
-                let needs_dir_pick = file.is_none();
+                let (file, load_error) = settings::load_or_backup_invalid();
+                let needs_dir_pick = file.is_none() && load_error.is_none();
                 let effective = settings::merge(&cli, &matches, file);
                 app::run_gui(app::GuiFlags {
                     server: effective.server,
                     ui: effective.ui,
                     needs_dir_pick,
+                    load_error,
+                    profile_locked: cli.config_path.is_some(),
                 })?;
                 Ok(())
             }
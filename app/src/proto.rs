@@ -9,16 +9,85 @@ pub struct ClientConfig {
     pub enableFileSizeLimit: Option<bool>,
     pub maxFileSize: Option<u64>,
     pub pathRegex: Option<Vec<String>>,
+    /// 客户端能解码的 `encoding`（如 `["base64", "utf8"]`）；未声明时服务端只发送 base64
+    pub encodings: Option<Vec<String>>,
+    /// 客户端能解压的算法，按优先级排列（如 `["zstd", "gzip"]`）；未声明时不压缩
+    pub compression: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ConnectionRejected {
     pub message: String,
+    /// 版本不兼容时提示需要升级哪一端
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upgradeHint: Option<String>,
+}
+
+/// 客户端连接后发送的 `hello`：版本与能力列表（`required` 为服务端必须支持的能力）。
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClientHello {
+    pub clientVersion: Option<String>,
+    /// 未提供时与不发送 `hello` 的旧版脚本相同，视为协议 1
+    #[serde(default = "legacy_protocol")]
+    pub protocol: u32,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    #[serde(default)]
+    pub required: Vec<String>,
+    /// 断线重连时带上上次 `hello` 回复中的 `sessionId` 以恢复会话
+    pub sessionId: Option<String>,
+    /// 客户端持久化的设备 id 与用户可读的设备名
+    pub deviceId: Option<String>,
+    pub deviceName: Option<String>,
+}
+
+fn legacy_protocol() -> u32 {
+    1
+}
+
+impl Default for ClientHello {
+    fn default() -> Self {
+        Self {
+            clientVersion: None,
+            protocol: legacy_protocol(),
+            capabilities: Vec::new(),
+            required: Vec::new(),
+            sessionId: None,
+            deviceId: None,
+            deviceName: None,
+        }
+    }
+}
+
+impl ClientHello {
+    pub fn device(&self) -> Option<crate::config::KnownDevice> {
+        let id = self.deviceId.as_deref().map(str::trim).filter(|id| !id.is_empty())?;
+        Some(crate::config::KnownDevice {
+            id: id.to_string(),
+            name: self.deviceName.as_deref().unwrap_or_default().trim().to_string(),
+        })
+    }
+}
+
+/// 服务端回复的 `hello`：`negotiated` 为双方都支持的能力。
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ServerHello {
+    pub serverVersion: String,
+    pub protocol: u32,
+    pub minProtocol: u32,
+    pub capabilities: Vec<String>,
+    pub negotiated: Vec<String>,
+    /// 协商了 `resume` 时下发，重连时在 `hello` 中带回
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sessionId: Option<String>,
+    pub resumed: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UpdateFile {
     pub path: String,
+    /// `encoding` 为 binary 时内容在二进制附件中，此字段为空
+    #[serde(default)]
     pub content: String,
     pub encoding: Option<String>,
 }
@@ -43,12 +112,16 @@ pub struct ChunkStart {
     pub totalChunks: u32,
     pub totalSize: Option<u64>,
     pub isDir: Option<bool>,
+    /// 所有分片 `content` 的编码（压缩时对整个文件压缩后再分片），未提供时为 base64
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ChunkData {
     pub fileId: String,
     pub chunkIndex: u32,
+    #[serde(default)]
     pub content: String,
     pub path: Option<String>,
 }
@@ -82,13 +155,104 @@ pub struct SyncControl {
     pub path: String,
     pub content: Option<String>,
     pub isDir: bool,
+    /// `sync_path` 的同步范围（相对路径）；整个目录同步时不发送该字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Vec<String>>,
+}
+
+/// 客户端 `sync_path` 请求：`path` 与 `paths` 任选其一或同时提供
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SyncPathRequest {
+    pub path: Option<String>,
+    pub paths: Option<Vec<String>>,
+}
+
+impl SyncPathRequest {
+    pub fn into_paths(self) -> Vec<String> {
+        self.path.into_iter().chain(self.paths.unwrap_or_default()).collect()
+    }
+}
+
+/// 客户端 `list_dir` / `fetch_file` 请求：`path` 为同步目录下的相对路径（根目录为空串）
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RemotePathRequest {
+    #[serde(default)]
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteEntry {
+    pub name: String,
+    pub path: String,
+    pub isDir: bool,
+    pub size: u64,
+    /// 修改时间（Unix 毫秒）
+    pub mtime: Option<u64>,
+    /// 命中过滤规则/大小限制，`fetch_file` 会被拒绝
+    pub filtered: bool,
+    pub reason: Option<String>,
+}
+
+/// `list_dir` 的响应（事件 `dir_listing`）；失败时 `error` 有值、`entries` 为空
+#[derive(Debug, Clone, Serialize)]
+pub struct DirListing {
+    pub action: String,
+    pub path: String,
+    pub entries: Vec<RemoteEntry>,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ChunkReceiveState {
     pub abs_path: std::path::PathBuf,
+    /// 分片写入的位置：压缩上传时为临时文件，解压成功后才替换 `abs_path`
+    pub write_path: std::path::PathBuf,
     pub rel_path: String,
     pub received_chunks: u32,
     pub total_chunks: u32,
     pub reject_reason: Option<String>,
+    pub encoding: crate::server::atoms::atom_helper_encoding::WireEncoding,
+}
+
+/// 一次“客户端上传全部”的进度统计（`client_upload_start` 到 `client_upload_complete`）。
+#[derive(Debug, Clone)]
+pub struct UploadSession {
+    pub started: std::time::Instant,
+    pub files: u32,
+    pub dirs: u32,
+    pub bytes: u64,
+    /// 服务端请求上传前为同步目录拍的快照
+    pub snapshot: Option<std::path::PathBuf>,
+}
+
+/// 当前连接握手协商的结果；未握手（旧版脚本）时为 `None`。
+#[derive(Debug, Clone)]
+pub struct PeerCapabilities {
+    pub client_version: Option<String>,
+    pub protocol: u32,
+    pub capabilities: Vec<String>,
+}
+
+impl PeerCapabilities {
+    pub fn has(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+/// 断线后保留的会话（宽限期内凭 `sessionId` 重连可恢复）。
+#[derive(Debug, Clone)]
+pub struct SuspendedSession {
+    pub id: String,
+    /// 会话只能由同一设备（`hello.deviceId`）恢复
+    pub device_id: Option<String>,
+    pub expires: std::time::Instant,
+    pub client_config: ClientConfig,
+    pub chunk_receive_state: std::collections::HashMap<String, ChunkReceiveState>,
+    /// 断线时仍在回声抑制期内的路径（恢复后重新计时）
+    pub client_written: Vec<String>,
+    /// 离线期间的本地变更，同一路径只保留最后一次
+    pub queued: Vec<crate::watcher::FsEvent>,
+    pub overflowed: bool,
+    /// 断线时仍有服务端发送的同步在进行（恢复后不续传）
+    pub sync_interrupted: bool,
 }
@@ -3,7 +3,7 @@
 //! 目标：集中描述 server 侧 atoms/molecules 的“契约”（签名/输入输出/副作用/错误模型），不写实现。
 //!
 //! 分层说明：
-//! - L2: `server/mod.rs`（启动/组装/生命周期）
+//! - L2: `server/mod.rs`（启动/组装/生命周期；`ServerCommand` 控制通道用于运行中热更新）
 //! - L3: `server/molecules/*`（业务动作：同步、上传处理、广播、Socket.IO 事件注册）
 //! - L4: `server/atoms/*`（最小可信实现单元；其中 `atom_helper_*` 为纯计算 helper）
 //!
@@ -14,35 +14,163 @@
 //! - `pub(crate) fn on_connect(socket: SocketRef, Data(data): Data<Value>, State(state): State<RuntimeState>)`
 //!   - 副作用：注册 Socket.IO 事件、写日志、可能断开连接
 //!   - 错误模型：对协议解码失败做忽略（不 panic），对业务失败写日志并 emit `sync_error`
+//!   - 开启 `require_device_approval` 时先交给 `device_approval::gate`；`admit(state, socket)` 设为当前客户端，
+//!     已有客户端时交给 `client_takeover::on_busy`；`accept(state, socket)` 注册事件（替换/确认后的新连接也经由这里）
+//!   - 断开时只清理仍是当前客户端的 socket（被替换的旧连接已在 `evict` 中清理）
+//!   - `hello`：协商版本/能力并存入 `RuntimeState.peer`（断连时清空）后回复 `hello`；不兼容时 emit `connection_rejected`（含 `upgradeHint`）并断开
+//!   - 带 `deviceId` 时日志的 `client` 字段改为设备名，并发送 `UiEvent::ClientIdentified`；随后 `device_rules::on_identified`
+//!   - `configure` 合并后调用 `device_rules::remember`
+//!   - 协商了 `resume` 时：带 `sessionId` 则恢复会话并在回复后重放离线变更，否则丢弃保留的会话并分配新的 `sessionId`
 //!
 //! `molecules/sync_all.rs`
-//! - `pub async fn run(state: &RuntimeState) -> Result<()>`
-//!   - 含 IO：遍历目录、计算过滤、发送更新/分片
+//! - `pub fn normalize_scope(base, paths) -> Result<Vec<String>, String>`
+//!   - 规范化子路径列表（去重、合并被父目录包含的路径）；目录外（含经符号链接解析后在目录外）/含 `..`/不存在时返回原因；空列表表示整个目录
+//! - `pub fn start(state, paths, trigger) -> Result<(), String>`
+//!   - 后台执行 `run`；客户端 `sync_all`/`sync_path`、GUI/托盘/stdin/HTTP 主动推送共用；未连接、路径非法或已在进行中时返回原因
+//! - `pub async fn run(state: &RuntimeState, scope: &[String]) -> Result<()>`
+//!   - 含 IO：遍历目录（或只遍历 `scope` 中的子路径，上级目录命中过滤规则时跳过）、计算过滤、发送更新/分片
+//!   - 按 20% 步长写进度日志并发送 `UiEvent::SyncProgress`；`sync_start`/`sync_complete` 携带 `scope`
+//!   - 发送前经 `tree_quota::measure` 统计待同步总量，超出配额时直接返回错误（由 `start` 以 `sync_error` 告知客户端）
+//!
+//! `molecules/upload_request.rs`
+//! - `pub fn request(state) -> Result<(), &'static str>`
+//!   - 先拍快照（失败则不发请求）再 emit `request_upload`；10s 内客户端未开始上传则写警告日志并放弃该请求；
+//!     未连接、正在上传或已有请求在拍快照/等待响应（`upload_requesting`/`pending_snapshot`）时返回原因
+//! - `on_upload_start/record_file/record_dir/on_upload_complete`：统计客户端上传进度（每 20 个文件一条日志），完成时汇总并附快照路径
+//! - `on_disconnect`：上传未完成即断开时提示快照路径
+//!
+//! `molecules/client_takeover.rs`
+//! - `on_busy(state, socket)`：按 `ServerConfig.takeover` 拒绝、替换旧连接或进入等待确认（`takeover_pending`，30s 超时拒绝）
+//! - `resolve(state, accept)`：处理等待确认的连接（允许时替换并 emit `takeover_accepted`）；没有等待的连接时返回原因
+//! - `disconnect_client(state)` / `evict`：断开当前客户端（不保留会话），被断开方收到 `connection_rejected`
+//! - `device_label`：User-Agent + socket id，用于日志与 GUI
+//!
+//! `molecules/device_approval.rs`
+//! - `gate(socket)`：只注册 `hello`；不兼容或未带 `deviceId` 时拒绝，已信任设备直接接受，其余进入等待确认（`device_pending`，60s 超时拒绝）
+//!   - 接受后（设为当前客户端时）emit `device_approved`，客户端需重新发送 `configure`/`hello`
+//! - `resolve(state, accept, remember) -> Result<(), &'static str>`：处理等待确认的设备；`remember` 时写入
+//!   `ServerConfig.trusted_devices` 与配置文件（`settings::trust_device`）并发送 `UiEvent::DeviceTrusted`
+//!
+//! `molecules/device_rules.rs`
+//! - `on_identified(state, device)`：记录当前设备；有记住的规则时以其为底叠加本次已收到的 `configure` 并重建过滤规则
+//! - `remember(state)`：当前设备的规则（过滤/大小限制，不含编码与压缩）有变化时写入 `ServerConfig.device_rules`
+//!   与配置文件（`settings::store_device_rules`），并发送 `UiEvent::DeviceRulesStored`；只记录已信任或本次连接经确认允许
+//!   （`approved_device`）的设备
+//! - `persist(state, failure, write)`：在 `spawn_blocking` 中写配置文件，失败时记录警告（信任设备也使用）
+//! - GUI 修改当前设备的规则后经 `apply_server_config` 立即覆盖当前客户端配置
+//!
+//! `molecules/tree_quota.rs`
+//! - `measure(state, scope) -> Result<TreeTotals>`：在阻塞线程池中只计数（`PathChecker::count_scoped`，不读内容、不记录条目），
+//!   设置了配额时超出即停止；整个目录时同时发送 `UiEvent::TreeSize`
+//! - `check(state, totals) -> Result<(), String>`：按 `quota_bytes`/`quota_files` 判断，超出时返回提示文案
+//! - `spawn_monitor(state, show_size)`：只在设置了配额或 GUI 显示总量时统计：启动时一次（超出配额写警告日志），
+//!   之后 `tree_size_dirty` 被置位时每 5s 内重新统计
+//!
+//! `molecules/session_resume.rs`
+//! - `issue(state) -> Option<String>`：为协商了 `resume` 的连接分配随机会话 id（`token::random_token`）；生成失败时写警告日志并返回 `None`（不支持恢复）
+//! - `suspend(state)`：断连时按 `resume_grace_secs` 保留客户端配置/分片接收状态/回声抑制路径，并绑定当前设备 id、记录同步是否被打断；到期后自动清理
+//! - `queue_change(state, ev)`：保留期间记录本地变更（同一路径只保留最后一次，超过上限后标记溢出）
+//! - `restore(state, id, device_id) -> Result<Vec<FsEvent>, String>`：恢复会话并返回待重放的变更；会话不存在/已过期/设备不一致时返回原因；
+//!   不续传断线时进行中的同步，只发送 `server_log` 提示重新下载
+//! - `replay(state, events)`：通过 `fs_broadcast::handle_fs_event` 重放；`discard`：丢弃未恢复的会话
+//! - 会话到期、被丢弃或恢复时已过期：经 `state::remove_chunk_temp_files` 删除其中未完成的压缩上传临时文件
+//!
+//! `molecules/remote_browse.rs`
+//! - `pub async fn list_dir(state, raw) -> Result<DirListing, String>`：列出一层子条目（大小/修改时间/是否被过滤及原因）；含 IO
+//! - `pub async fn fetch_file(state, raw) -> Result<(), String>`：按需发送单个文件（`update`/分片）；被过滤、超限、不存在时返回原因
+//! - 两者都逐级检查上级目录（`PathChecker::filtered_ancestor`），并用 `path_check::resolve_within` 拒绝经符号链接指向目录外的路径
+//! - `handle_list_dir/handle_fetch_file`：socket 事件入口，分别 emit `dir_listing` / 失败时 emit `fetch_error`
 //!
 //! `molecules/client_upload.rs`
 //! - `handle_update/create_dir/chunk_*`：处理客户端上传与分片 ACK；含 IO（写文件/创建目录）
-//! - `disconnect_cleanup`：断连时清理状态，停止分片重试/遍历等
+//!   - `update`：未压缩内容先按解码后大小（`decoded_len`）检查限制再解码；压缩内容解压输出受 `decompress_limit` 约束
+//!   - 压缩上传的分片先按传输编码写入 `.packed.f11esync-upload` 临时文件，`handle_chunk_complete` 时在 `spawn_blocking` 中流式解压，
+//!     成功后才重命名为目标文件；失败或断连时只删除临时文件
+//! - `disconnect_cleanup`：断连时先保留会话（`session_resume::suspend`）再清理状态，停止分片重试/遍历等
+//!
+//! `molecules/path_check.rs`
+//! - `PathChecker::check/check_rel`：按“内置忽略 -> 服务端规则 -> 客户端规则 -> 大小限制”判定路径，返回命中的规则/原因
+//! - `PathChecker::preview`：预演 `sync_all`（被过滤目录不再深入），统计文件/目录/字节/分片数与每个跳过原因，不 emit；含 IO（遍历目录；可能分片的文件按协商编码读取并编码一次）
+//! - `PathChecker::preview_scoped`：只预演指定子路径
+//! - `PathChecker::count_scoped(dir, scope, stop)`：与预演相同的遍历但只计数（`TreeTotals`），`stop` 返回 true 时提前结束（`truncated`）
+//! - `PathChecker::filtered_ancestor`：第一个命中过滤规则的上级目录
+//! - `resolve_within(base, rel)`：解析符号链接后仍在同步目录内的真实路径；含 IO
+//! - `PathChecker::dry_run`：`preview` 中会被跳过的条目
+//! - `PathChecker::from_state`：直接使用运行中服务正在生效的规则（`effective_regex`）、大小限制与当前客户端配置
+//!
+//! `molecules/http_api.rs`
+//! - 所有 `/api/*` 路由经 `require_token` 校验 `Authorization: Bearer <口令>`（`settings::load_or_create_control_token`，401），且不加跨域头
+//! - `GET /api/sync/preview`：以实时配置返回 `SyncPreview`（JSON）
+//! - `POST /api/sync/push`：主动推送全部文件，或请求体 `{"paths": [...]}` 中的子路径（202；路径非法 400；未连接/进行中 409）
+//! - `POST /api/upload/request`：请求客户端上传全部文件（202；未连接/正在上传/已在请求中 409）
 //!
 //! `molecules/fs_broadcast.rs`
-//! - `handle_fs_event`：本地文件变化后广播给客户端（遵循过滤+大小限制）
+//! - `handle_fs_event`：本地文件变化后广播给客户端（遵循过滤+大小限制）；未连接时交给 `session_resume::queue_change`
+//!   - 新建目录：启用配额时先统计该目录，超出时跳过并 emit `sync_error`
 //!
 //! ---------------------------------------------------------------------------
 //! L4 · Atoms
 //! ---------------------------------------------------------------------------
 //! `atoms/state.rs`
 //! - `set_socket_if_empty`：设置单客户端 socket（拒绝第二个客户端）
+//! - `take_socket` / `is_current_socket`：替换/断开客户端时取出当前 socket、判断断开的是否为当前客户端
+//! - `connection_generation` / `is_same_connection`：连接代号（设置/替换/清除 socket 时递增）；`sync_all`/`sync_path`、分片发送、目录遍历与会话重放在开始时记录，连接更换后停止
+//! - `reset_connection_state` / `remove_chunk_temp_files`：断连时清理连接状态；删除未完成的压缩上传临时文件（`write_path != abs_path`）
 //! - `merge_client_config`：合并 client config（只覆盖提供字段）
+//! - `peer_supports`：当前连接是否协商了某项能力（未握手时为 false）
+//! - `codec_client_config`：发送文件用的 client config（`configure` 声明 + 握手协商的编码/压缩）
 //! - `rebuild_effective_regex`：断连/重连时维护“服务端规则 + 客户端规则”合并结果
+//! - `apply_server_config`：运行中热更新过滤规则/大小限制并重建生效规则（不断开客户端）；地址/目录变更仅提示需重启
+//! - `FlagGuard::try_set(flag)`：独占标志（`sync_running`/`upload_requesting`），释放时（包括 panic）清除
+//! - `ui_log` / `ui_log_with`：写 GUI 日志并同时写入 tracing（结构化字段 action/path/bytes/client）
 //!
 //! `atoms/socket_emit.rs`
 //! - `emit_*`：所有对客户端的 Socket.IO emit，副作用：网络发送
+//!   - `emit_update_small` / `send_chunk_and_wait_ack`：`binary` 编码时内容作为事件的第二个参数（二进制附件）发送，`content` 为空
+//!   - `send_chunk_and_wait_ack`：协商了 `ack` 时用 `emit_with_ack` 等待原生确认（5s 超时，按回复中的 `success` 判定），否则等待 `chunk_ack` 事件（`chunk_ack_waiters`）
+//!
+//! `atoms/snapshot.rs`
+//! - `snapshot_dir(src, root, keep, include) -> Result<(PathBuf, u64)>`：复制 `include` 接受的条目到 `root/<目录名>-<时间戳>[-n]`（不复用已有目录），
+//!   只保留最近 `keep` 份；跳过位于同步目录内的 `root`，超过 `MAX_SNAPSHOT_BYTES` 时放弃并清理；含 IO
+//!   - `upload_request` 以 `PathChecker::from_state` 的判定作为 `include`
+//!
+//! `atoms/atom_helper_encoding.rs`
+//! - 纯计算：`WireEncoding::parse/decode`（可选 `zstd+`/`gzip+` 前缀 + `base64`/`utf8`/`binary`，未提供时按 base64；其它编码返回错误，调用方发送 `server_log` 警告并拒绝）
+//! - `encode_for_client`：按客户端 `configure.encodings` 选择：合法 UTF-8 优先纯文本，其次 `binary`（Socket.IO 二进制附件），否则 base64
+//!   - 协商了 `configure.compression` 时整文件压缩，仅在实际发送体积更小时采用（≥1KB 的文件才尝试）
+//! - `Compression::decompress_to/decompress`：流式解压，输出超过 `limit` 立即返回错误；上限来自 `atom_helper_limits::decompress_limit`（生效的大小限制，最多 `MAX_DECOMPRESSED_SIZE`）
+//! - `EncodedContent::chunks` / `split_chunks`：按字节切分分片（文本不拆开 UTF-8 字符，二进制以 `Bytes` 切片，发送与重试只增加引用计数、不复制数据）
+//!
+//! `atoms/atom_helper_protocol.rs`
+//! - 纯计算：`negotiate(hello) -> Result<PeerCapabilities, Rejection>`（协议版本过旧（缺少 `protocol` 时按协议 1 处理）或缺少 `required` 能力时返回原因与升级提示；协商能力为双方交集）
+//! - `apply_codecs`：把协商的 `utf8`/`binary`/`zstd`/`gzip` 并入 `encodings`/`compression`
 //!
 //! `atoms/atom_helper_filter.rs`
-//! - 纯计算：路径过滤匹配
+//! - 纯计算：路径过滤匹配、规则编译、`invalid_regexes` 校验（返回序号/规则/错误摘要）
+//! - `effective_regexes(server, client)`：服务端在前、客户端在后的生效规则；`rebuild_effective_regex` 与 `PathChecker` 共用
+//! - 服务端规则非法时 `run_server` 直接返回错误、热更新被拒绝；客户端 `pathRegex` 非法时发送 `server_log` 警告
+//!
+//! `atoms/atom_helper_path.rs`
+//! - 纯计算：客户端/命令行路径转相对路径（拒绝目录外与 `..`）、拼接子路径、`ancestors`（各级上级目录）
 //!
 //! `atoms/atom_helper_limits.rs`
 //! - 纯计算：服务端/客户端文件大小限制合并（取更小值）
+//! - `chunk_count(content)`：编码结果（`encode_for_client`）实际发送的分片数；`fits_single_chunk(size)`：按 base64 上界判断无需分片
+//! - `validate_file_size(rel, size, client, cfg) -> Option<String>`：服务端限制取 `size_rules` 中第一条匹配的规则，
+//!   否则为启用时的全局限制；提示文案写明生效的规则
+//! - `effective_limit(rel, client, cfg)` / `decompress_limit(...)`：生效的大小上限；解压上限在未设置限制时为 `MAX_DECOMPRESSED_SIZE`
+//! - `rule_matches(pattern, rel)`：扩展名（`.ts`，不区分大小写）或 glob（不含 `/` 时只匹配文件名，`**` 跨目录）；glob 用动态规划匹配，多个 `**` 也不会指数回溯
+//! - `check_quota(files, bytes, cfg) -> Result<(), String>` / `human_bytes(bytes)`：总量配额判断与字节数格式化
 //!
 //! `atoms/atom_helper_messages.rs`
 //! - 纯计算：日志/提示文案（不做 IO）
+//!
+//! `atoms/token.rs`
+//! - `random_token() -> Result<String>`：64 位十六进制随机口令（控制接口口令、会话 id），取自操作系统安全随机源（`getrandom`），不可用时返回错误；副作用：系统随机源
+//!
+//! `atoms/lan.rs`
+//! - `reachable_urls`：枚举本机非回环网卡地址，生成手机可访问的 `http://<ip>:<port>`；副作用：查询系统网卡
+//!
+//! `atoms/atom_helper_pairing.rs`
+//! - 纯计算：URL 格式化、二维码内容模板替换、终端二维码渲染
 
@@ -0,0 +1,339 @@
+use crate::proto::ClientConfig;
+use crate::server::atoms::atom_helper_limits;
+use base64::Engine;
+use bytes::Bytes;
+use std::io::{Read, Write};
+
+/// 服务端可解码的传输编码（客户端上传时使用），可加压缩前缀，如 `zstd+base64`
+pub const ACCEPTED: &[&str] = &["base64", "utf8", "binary"];
+/// 服务端支持的压缩算法
+pub const COMPRESSIONS: &[&str] = &["zstd", "gzip"];
+/// 小于该大小的文件不尝试压缩
+const COMPRESS_MIN_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Base64,
+    Utf8,
+    /// 内容以 Socket.IO 二进制附件发送（事件的第二个参数），`content` 为空
+    Binary,
+}
+
+impl ContentEncoding {
+    fn parse_name(raw: &str) -> Option<Self> {
+        match raw {
+            "base64" => Some(Self::Base64),
+            "utf8" | "utf-8" => Some(Self::Utf8),
+            "binary" => Some(Self::Binary),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Base64 => "base64",
+            Self::Utf8 => "utf8",
+            Self::Binary => "binary",
+        }
+    }
+
+    /// 解码后的字节数，无需实际解码（用于先检查大小限制）。
+    pub fn decoded_len(self, content: &str, attachment: Option<&[u8]>) -> u64 {
+        match self {
+            Self::Base64 => {
+                let padding = content.bytes().rev().take_while(|&b| b == b'=').count().min(2);
+                (content.len() / 4 * 3).saturating_sub(padding) as u64
+            }
+            Self::Utf8 => content.len() as u64,
+            Self::Binary => attachment.map_or(0, |bytes| bytes.len() as u64),
+        }
+    }
+
+    pub fn decode(self, content: &str, attachment: Option<&[u8]>) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Base64 => base64::engine::general_purpose::STANDARD
+                .decode(content)
+                .map_err(|_| "Base64 解码失败".to_string()),
+            Self::Utf8 => Ok(content.as_bytes().to_vec()),
+            Self::Binary => attachment.map(<[u8]>::to_vec).ok_or_else(|| "缺少二进制附件".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Zstd,
+    Gzip,
+}
+
+impl Compression {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "zstd" => Some(Self::Zstd),
+            "gzip" => Some(Self::Gzip),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Gzip => "gzip",
+        }
+    }
+
+    pub fn compress(self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Self::Zstd => zstd::stream::encode_all(bytes, 3),
+            Self::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    /// 流式解压到 `writer`，最多输出 `limit` 字节；超过时立即停止并返回错误，成功时返回解压后的大小。
+    pub fn decompress_to(self, reader: impl Read, writer: &mut impl Write, limit: u64) -> Result<u64, String> {
+        let failed = |err: std::io::Error| format!("{} 解压失败: {err}", self.as_str());
+        let written = match self {
+            Self::Zstd => {
+                let decoder = zstd::stream::read::Decoder::new(reader).map_err(failed)?;
+                std::io::copy(&mut decoder.take(limit + 1), writer)
+            }
+            Self::Gzip => std::io::copy(&mut flate2::read::GzDecoder::new(reader).take(limit + 1), writer),
+        }
+        .map_err(failed)?;
+        if written > limit {
+            return Err(format!("解压后超过 {}，已拒绝", atom_helper_limits::human_bytes(limit)));
+        }
+        Ok(written)
+    }
+
+    pub fn decompress(self, bytes: &[u8], limit: u64) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        self.decompress_to(bytes, &mut out, limit)?;
+        Ok(out)
+    }
+}
+
+/// 线上的 `encoding` 字段：可选的压缩 + 传输编码，如 `base64`、`utf8`、`zstd+binary`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WireEncoding {
+    pub compression: Option<Compression>,
+    pub transport: ContentEncoding,
+}
+
+impl WireEncoding {
+    pub const BASE64: Self = Self { compression: None, transport: ContentEncoding::Base64 };
+
+    /// 未提供 `encoding` 时按 base64 处理（旧版脚本始终使用 base64）；压缩数据不能使用 utf8 传输。
+    pub fn parse(raw: Option<&str>) -> Result<Self, String> {
+        let raw = raw.unwrap_or("base64");
+        let unsupported = || {
+            format!(
+                "不支持的编码 `{raw}`（支持: {}，可加压缩前缀 {}+）",
+                ACCEPTED.join(", "),
+                COMPRESSIONS.join("+/")
+            )
+        };
+        let (compression, transport) = match raw.split_once('+') {
+            Some((c, t)) => (Some(Compression::parse(c).ok_or_else(unsupported)?), t),
+            None => (None, raw),
+        };
+        let transport = ContentEncoding::parse_name(transport).ok_or_else(unsupported)?;
+        if compression.is_some() && transport == ContentEncoding::Utf8 {
+            return Err(unsupported());
+        }
+        Ok(Self { compression, transport })
+    }
+
+    pub fn name(self) -> String {
+        match self.compression {
+            Some(c) => format!("{}+{}", c.as_str(), self.transport.as_str()),
+            None => self.transport.as_str().to_string(),
+        }
+    }
+
+    /// 解码完整内容（传输编码 + 解压），解压输出不超过 `limit` 字节。
+    pub fn decode(self, content: &str, attachment: Option<&[u8]>, limit: u64) -> Result<Vec<u8>, String> {
+        let bytes = self.transport.decode(content, attachment)?;
+        match self.compression {
+            Some(c) => c.decompress(&bytes, limit),
+            None => Ok(bytes),
+        }
+    }
+}
+
+/// 待发送的文件内容：`binary` 有值时内容在二进制附件中（`text` 为空串），否则在 `text` 中。
+#[derive(Debug, Clone)]
+pub struct EncodedContent {
+    pub encoding: WireEncoding,
+    pub text: String,
+    /// `Bytes` 切片与克隆只增加引用计数，分片发送/重试不复制数据
+    pub binary: Option<Bytes>,
+}
+
+impl EncodedContent {
+    fn pack(bytes: Vec<u8>, compression: Option<Compression>, binary: bool) -> Self {
+        if binary {
+            return Self {
+                encoding: WireEncoding { compression, transport: ContentEncoding::Binary },
+                text: String::new(),
+                binary: Some(Bytes::from(bytes)),
+            };
+        }
+        Self {
+            encoding: WireEncoding { compression, transport: ContentEncoding::Base64 },
+            text: base64::engine::general_purpose::STANDARD.encode(bytes),
+            binary: None,
+        }
+    }
+
+    /// 实际发送的字节数（用于判断是否需要分片、是否值得压缩）
+    pub fn wire_len(&self) -> usize {
+        self.binary.as_ref().map_or(self.text.len(), Bytes::len)
+    }
+
+    /// 分片后每片的 `(content, 二进制附件)`，每片不超过 `max` 字节。
+    pub fn chunks(&self, max: usize) -> Vec<(&str, Option<Bytes>)> {
+        match &self.binary {
+            Some(bytes) => (0..bytes.len())
+                .step_by(max)
+                .map(|start| ("", Some(bytes.slice(start..(start + max).min(bytes.len())))))
+                .collect(),
+            None => split_chunks(&self.text, max).into_iter().map(|c| (c, None)).collect(),
+        }
+    }
+}
+
+/// 按客户端 `configure` 声明选择编码：合法 UTF-8 优先纯文本，其次二进制附件，否则 base64；
+/// 协商了压缩时对整个文件压缩，仅在实际发送体积更小时采用。
+pub fn encode_for_client(bytes: Vec<u8>, client: &ClientConfig) -> EncodedContent {
+    let supports = |name: &str| client.encodings.as_ref().is_some_and(|list| list.iter().any(|e| e == name));
+    let compressed = client
+        .compression
+        .iter()
+        .flatten()
+        .find_map(|name| Compression::parse(name))
+        .filter(|_| bytes.len() >= COMPRESS_MIN_SIZE)
+        .and_then(|c| c.compress(&bytes).ok().map(|data| EncodedContent::pack(data, Some(c), supports("binary"))));
+
+    let plain = if supports("utf8") {
+        match String::from_utf8(bytes) {
+            Ok(text) => EncodedContent {
+                encoding: WireEncoding { compression: None, transport: ContentEncoding::Utf8 },
+                text,
+                binary: None,
+            },
+            Err(err) => EncodedContent::pack(err.into_bytes(), None, supports("binary")),
+        }
+    } else {
+        EncodedContent::pack(bytes, None, supports("binary"))
+    };
+
+    match compressed {
+        Some(packed) if packed.wire_len() < plain.wire_len() => packed,
+        _ => plain,
+    }
+}
+
+/// 按字节长度切分分片，每片不超过 `max` 字节且不拆开 UTF-8 字符（base64 均为 ASCII，按固定长度切分）。
+pub fn split_chunks(content: &str, max: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = content;
+    while !rest.is_empty() {
+        let mut end = max.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (head, tail) = rest.split_at(end);
+        chunks.push(head);
+        rest = tail;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(encodings: &[&str], compression: &[&str]) -> ClientConfig {
+        ClientConfig {
+            encodings: Some(encodings.iter().map(|e| e.to_string()).collect()),
+            compression: Some(compression.iter().map(|c| c.to_string()).collect()),
+            ..ClientConfig::default()
+        }
+    }
+
+    fn round_trip(bytes: &[u8], client: &ClientConfig) -> WireEncoding {
+        let encoded = encode_for_client(bytes.to_vec(), client);
+        let parsed = WireEncoding::parse(Some(&encoded.encoding.name())).unwrap();
+        assert_eq!(parsed, encoded.encoding);
+        let decoded = parsed.decode(&encoded.text, encoded.binary.as_deref(), u64::MAX - 1).unwrap();
+        assert_eq!(decoded, bytes);
+        parsed
+    }
+
+    #[test]
+    fn parse_names_round_trip() {
+        assert_eq!(WireEncoding::parse(None).unwrap(), WireEncoding::BASE64);
+        for name in ["base64", "utf8", "binary", "zstd+base64", "gzip+binary"] {
+            assert_eq!(WireEncoding::parse(Some(name)).unwrap().name(), name);
+        }
+        assert_eq!(WireEncoding::parse(Some("utf-8")).unwrap().transport, ContentEncoding::Utf8);
+    }
+
+    #[test]
+    fn parse_rejects_unknown() {
+        for name in ["hex", "lz4+base64", "zstd+utf8", "zstd+", "+base64"] {
+            assert!(WireEncoding::parse(Some(name)).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn encode_for_client_round_trips() {
+        let text = "你好，F11eSync\n".as_bytes();
+        let binary: Vec<u8> = (0..=255u8).chain([0xff, 0xfe]).collect();
+
+        assert_eq!(round_trip(text, &ClientConfig::default()), WireEncoding::BASE64);
+        assert_eq!(round_trip(text, &client(&["utf8", "binary"], &[])).transport, ContentEncoding::Utf8);
+        assert_eq!(round_trip(&binary, &client(&["utf8"], &[])), WireEncoding::BASE64);
+        assert_eq!(round_trip(&binary, &client(&["utf8", "binary"], &[])).transport, ContentEncoding::Binary);
+        assert_eq!(round_trip(b"", &client(&["binary"], &[])).transport, ContentEncoding::Binary);
+    }
+
+    #[test]
+    fn encode_for_client_compresses_when_smaller() {
+        let text = "a".repeat(8 * 1024).into_bytes();
+        for (name, compression) in [("zstd", Compression::Zstd), ("gzip", Compression::Gzip)] {
+            let enc = round_trip(&text, &client(&["utf8", "binary"], &[name]));
+            assert_eq!(enc, WireEncoding { compression: Some(compression), transport: ContentEncoding::Binary });
+            let enc = round_trip(&text, &client(&[], &[name]));
+            assert_eq!(enc, WireEncoding { compression: Some(compression), transport: ContentEncoding::Base64 });
+        }
+        // 太小的文件不压缩
+        assert_eq!(round_trip(b"aaaa", &client(&["utf8"], &["zstd"])).compression, None);
+    }
+
+    #[test]
+    fn decompress_stops_at_limit() {
+        let bytes = vec![0u8; 64 * 1024];
+        for compression in [Compression::Zstd, Compression::Gzip] {
+            let packed = compression.compress(&bytes).unwrap();
+            assert_eq!(compression.decompress(&packed, bytes.len() as u64).unwrap(), bytes);
+            assert!(compression.decompress(&packed, bytes.len() as u64 - 1).is_err());
+        }
+    }
+
+    #[test]
+    fn decoded_len_matches_decode() {
+        for len in 0..8 {
+            let bytes = vec![7u8; len];
+            let content = base64::engine::general_purpose::STANDARD.encode(&bytes);
+            assert_eq!(ContentEncoding::Base64.decoded_len(&content, None), len as u64);
+        }
+        assert_eq!(ContentEncoding::Utf8.decoded_len("你好", None), 6);
+        assert_eq!(ContentEncoding::Binary.decoded_len("", Some(&[1, 2, 3])), 3);
+    }
+}
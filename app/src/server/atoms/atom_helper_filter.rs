@@ -1,6 +1,41 @@
 use regex::Regex;
 
 pub fn should_filter_path(rel: &str, regexes: &[Regex]) -> bool {
+    first_match(rel, regexes).is_some()
+}
+
+/// 第一条命中的规则下标（`should_filter_path` 与规则测试共用）。
+pub fn first_match(rel: &str, regexes: &[Regex]) -> Option<usize> {
     let rel = rel.replace('\\', "/");
-    regexes.iter().any(|re| re.is_match(&rel))
+    regexes.iter().position(|re| re.is_match(&rel))
+}
+
+/// 编译规则列表；非法正则直接跳过（调用方应先用 `invalid_regexes` 校验并提示）。
+pub fn compile_regexes(patterns: &[String]) -> Vec<Regex> {
+    patterns.iter().filter_map(|s| Regex::new(s).ok()).collect()
+}
+
+/// 生效规则：服务端规则在前、客户端规则在后（非法规则跳过）；实际同步与规则测试/预演共用。
+pub fn effective_regexes(server: &[Regex], client: Option<&[String]>) -> Vec<Regex> {
+    let mut merged = server.to_vec();
+    if let Some(list) = client {
+        merged.extend(compile_regexes(list));
+    }
+    merged
+}
+
+/// 非法规则列表：`(序号, 规则, 错误摘要)`，序号从 1 开始。
+pub fn invalid_regexes(patterns: &[String]) -> Vec<(usize, String, String)> {
+    patterns
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| Regex::new(p).err().map(|err| (i + 1, p.clone(), regex_error_summary(&err))))
+        .collect()
+}
+
+/// regex 的错误信息是多行的（含原文与 `^` 标记），界面/日志只取最后一行说明。
+pub fn regex_error_summary(err: &regex::Error) -> String {
+    let text = err.to_string();
+    let last = text.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or(&text);
+    last.trim().trim_start_matches("error:").trim().to_string()
 }
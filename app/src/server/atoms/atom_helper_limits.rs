@@ -1,47 +1,191 @@
 use crate::config::ServerConfig;
 use crate::proto::ClientConfig;
+use crate::server::atoms::atom_helper_encoding::EncodedContent;
+
+/// 单个分片的最大长度（编码后的字节数）；不超过它的内容直接整包发送。
+pub const CHUNK_SIZE: usize = 256 * 1024;
+/// 未设置文件大小限制时，客户端上传的压缩内容解压后的最大大小（防止压缩炸弹）。
+pub const MAX_DECOMPRESSED_SIZE: u64 = 1 << 30;
+
+/// 发送编码后内容所需的分片数（与 `broadcast_file` 的切分一致）；整包发送时为 0。
+pub fn chunk_count(content: &EncodedContent) -> u32 {
+    if content.wire_len() <= CHUNK_SIZE {
+        return 0;
+    }
+    content.chunks(CHUNK_SIZE).len() as u32
+}
+
+/// base64 是最长的编码结果（压缩只在更小时采用），按它计算仍能整包发送时无需读取内容。
+pub fn fits_single_chunk(size: u64) -> bool {
+    size.div_ceil(3) * 4 <= CHUNK_SIZE as u64
+}
 
 fn kb(bytes: u64) -> String {
     format!("{:.1}KB", bytes as f64 / 1024.0)
 }
 
-pub fn validate_file_size(size: u64, client: &ClientConfig, cfg: &ServerConfig) -> Option<String> {
-    let server_limit = cfg
-        .enable_server_side_file_size_limit
-        .then_some(cfg.max_server_side_file_size);
+/// 按量级显示大小（B/KB/MB/GB），用于目录总量与配额提示。
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes}B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1}{}", UNITS[unit])
+}
+
+/// 待同步内容的总量配额；超出时返回包含实际数量与配额的说明。
+pub fn check_quota(files: u64, bytes: u64, cfg: &ServerConfig) -> Result<(), String> {
+    let over_files = cfg.quota_files.is_some_and(|max| files > max);
+    let over_bytes = cfg.quota_bytes.is_some_and(|max| bytes > max);
+    if !over_files && !over_bytes {
+        return Ok(());
+    }
+    let limits: Vec<String> = [
+        cfg.quota_files.map(|max| format!("{max} 个文件")),
+        cfg.quota_bytes.map(human_bytes),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    Err(format!(
+        "待同步 {files} 个文件、共 {}，超出配额（{}），已中止；请确认同步目录是否选对，或调大配额",
+        human_bytes(bytes),
+        limits.join(" / ")
+    ))
+}
+
+/// 大小规则是否匹配相对路径：扩展名写法（如 `.ts`）不区分大小写；glob 不含 `/` 时只匹配文件名。
+pub fn rule_matches(pattern: &str, rel: &str) -> bool {
+    let rel = rel.replace('\\', "/");
+    let name = rel.rsplit('/').next().unwrap_or_default();
+    if pattern.starts_with('.') && !pattern.contains(['*', '?', '/']) {
+        return name.to_lowercase().ends_with(&pattern.to_lowercase());
+    }
+    let target = if pattern.contains('/') { rel.as_str() } else { name };
+    let pattern: Vec<char> = pattern.trim_start_matches('/').chars().collect();
+    let target: Vec<char> = target.chars().collect();
+    glob_match(&pattern, &target)
+}
 
-    let client_limit = client
-        .enableFileSizeLimit
-        .unwrap_or(false)
-        .then_some(client.maxFileSize)
-        .flatten();
+/// `*`/`?` 不跨 `/`，`**` 跨目录（`**/` 也匹配零层目录）。
+/// 自后向前填表（`dp[i][j]`：`p[i..]` 是否匹配 `s[j..]`），多个 `**` 也是 O(|p|·|s|)。
+fn glob_match(p: &[char], s: &[char]) -> bool {
+    let mut dp = vec![vec![false; s.len() + 1]; p.len() + 1];
+    dp[p.len()][s.len()] = true;
+    for i in (0..p.len()).rev() {
+        if p[i] == '*' && p.get(i + 1) == Some(&'*') {
+            let rest = i + 2;
+            let mut any = false;
+            for j in (0..=s.len()).rev() {
+                any |= dp[rest][j];
+                let zero_dirs = p.get(rest) == Some(&'/') && dp[rest + 1][j];
+                dp[i][j] = zero_dirs || any;
+            }
+            continue;
+        }
+        for j in (0..=s.len()).rev() {
+            let c = s.get(j).copied();
+            dp[i][j] = match p[i] {
+                '*' => dp[i + 1][j] || (c.is_some_and(|c| c != '/') && dp[i][j + 1]),
+                '?' => c.is_some_and(|c| c != '/') && dp[i + 1][j + 1],
+                pc => c == Some(pc) && dp[i + 1][j + 1],
+            };
+        }
+    }
+    dp[0][0]
+}
+
+/// 服务端对该文件生效的限制：第一条匹配的大小规则，否则为启用时的全局限制；附带用于提示的规则名。
+fn server_limit(rel: &str, cfg: &ServerConfig) -> Option<(u64, String)> {
+    if let Some(rule) = cfg.size_rules.iter().find(|r| rule_matches(&r.pattern, rel)) {
+        return Some((rule.max_size, format!("规则 `{}`", rule.pattern)));
+    }
+    cfg.enable_server_side_file_size_limit
+        .then(|| (cfg.max_server_side_file_size, "服务端限制".to_string()))
+}
 
-    let effective_opt = match (server_limit, client_limit) {
+fn client_limit(client: &ClientConfig) -> Option<u64> {
+    client.enableFileSizeLimit.unwrap_or(false).then_some(client.maxFileSize).flatten()
+}
+
+/// 该文件生效的大小上限（服务端规则与客户端限制取较小者），未设置任何限制时为 `None`。
+pub fn effective_limit(rel: &str, client: &ClientConfig, cfg: &ServerConfig) -> Option<u64> {
+    match (server_limit(rel, cfg), client_limit(client)) {
         (None, None) => None,
-        (Some(s), None) => Some(s),
+        (Some((s, _)), None) => Some(s),
         (None, Some(c)) => Some(c),
-        (Some(s), Some(c)) => Some(s.min(c)),
-    };
-    let Some(effective) = effective_opt else {
-        return None;
-    };
+        (Some((s, _)), Some(c)) => Some(s.min(c)),
+    }
+}
 
+/// 解压客户端上传内容时允许的最大输出：生效的大小限制，且不超过解压硬上限。
+pub fn decompress_limit(rel: &str, client: &ClientConfig, cfg: &ServerConfig) -> u64 {
+    effective_limit(rel, client, cfg).map_or(MAX_DECOMPRESSED_SIZE, |limit| limit.min(MAX_DECOMPRESSED_SIZE))
+}
+
+pub fn validate_file_size(rel: &str, size: u64, client: &ClientConfig, cfg: &ServerConfig) -> Option<String> {
+    let effective = effective_limit(rel, client, cfg)?;
     if size <= effective {
         return None;
     }
 
-    let reason = match (server_limit, client_limit) {
-        (Some(s), Some(c)) => format!(
-            "文件过大 ({})，已跳过（服务端限制：{}，客户端限制：{}，生效：{}）",
+    let reason = match (server_limit(rel, cfg), client_limit(client)) {
+        (Some((s, rule)), Some(c)) => format!(
+            "文件过大 ({})，已跳过（{rule}：{}，客户端限制：{}，生效：{}）",
             kb(size),
             kb(s),
             kb(c),
             kb(effective)
         ),
-        (Some(s), None) => format!("文件过大 ({})，已跳过（服务端限制：{}）", kb(size), kb(s)),
+        (Some((s, rule)), None) => format!("文件过大 ({})，已跳过（{rule}：{}）", kb(size), kb(s)),
         (None, Some(c)) => format!("文件过大 ({})，已跳过（客户端限制：{}）", kb(size), kb(c)),
         (None, None) => unreachable!(),
     };
 
     Some(reason)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::rule_matches;
+
+    #[test]
+    fn double_star_matches_zero_or_more_dirs() {
+        assert!(rule_matches("images/**/*.png", "images/a.png"));
+        assert!(rule_matches("images/**/*.png", "images/x/y/a.png"));
+        assert!(rule_matches("**/*.png", "a.png"));
+        assert!(rule_matches("images/**", "images/x/y/a.bin"));
+        assert!(!rule_matches("**/b", "ab"));
+        assert!(!rule_matches("images/**/*.png", "other/images/a.png"));
+    }
+
+    #[test]
+    fn single_star_and_question_do_not_cross_slash() {
+        assert!(rule_matches("docs/*.md", "docs/a.md"));
+        assert!(!rule_matches("docs/*.md", "docs/sub/a.md"));
+        assert!(rule_matches("docs/?.md", "docs/a.md"));
+        assert!(!rule_matches("docs?a.md", "docs/a.md"));
+        // 不含 `/` 的 glob 只匹配文件名
+        assert!(rule_matches("*.md", "docs/sub/a.md"));
+    }
+
+    #[test]
+    fn extension_rules_ignore_case() {
+        assert!(rule_matches(".ts", "src/Main.TS"));
+        assert!(rule_matches(".TS", "src/main.ts"));
+        assert!(!rule_matches(".ts", "src/main.tsx"));
+    }
+
+    #[test]
+    fn many_double_stars_match_deep_paths() {
+        let pattern = "**/".repeat(12) + "*.x";
+        assert!(!rule_matches(&pattern, &("a/".repeat(40) + "b.y")));
+        assert!(rule_matches(&pattern, &("a/".repeat(40) + "b.x")));
+    }
+}
@@ -28,3 +28,25 @@ pub fn format_chunk_progress(
     }
     None
 }
+
+/// 同步范围的显示名：整个目录为“上传全部”，否则列出子路径。
+pub fn format_sync_scope(scope: &[String]) -> String {
+    if scope.is_empty() {
+        "上传全部".to_string()
+    } else {
+        format!("同步 {}", scope.join(", "))
+    }
+}
+
+pub fn format_hello(peer: &crate::proto::PeerCapabilities) -> String {
+    let caps = if peer.capabilities.is_empty() {
+        "无".to_string()
+    } else {
+        peer.capabilities.join(", ")
+    };
+    format!(
+        "客户端握手：版本 {}，协议 {}，协商能力: {caps}",
+        peer.client_version.as_deref().unwrap_or("未知"),
+        peer.protocol
+    )
+}
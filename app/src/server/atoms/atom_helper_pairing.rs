@@ -0,0 +1,51 @@
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use std::net::IpAddr;
+
+pub fn format_http_url(ip: IpAddr, port: u16) -> String {
+    match ip {
+        IpAddr::V4(v4) => format!("http://{v4}:{port}"),
+        IpAddr::V6(v6) => format!("http://[{v6}]:{port}"),
+    }
+}
+
+/// 排序权重：私网 IPv4 优先（手机最可能在同一局域网），其次其它 IPv4，最后 IPv6。
+pub fn ip_rank(ip: &IpAddr) -> u8 {
+    match ip {
+        IpAddr::V4(v4) if v4.is_private() => 0,
+        IpAddr::V4(_) => 1,
+        IpAddr::V6(_) => 2,
+    }
+}
+
+fn encode_query_component(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(b as char),
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+/// 二维码内容：未配置模板时直接是服务地址；模板中的 `{url}` 会被替换为 URL 编码后的服务地址，
+/// 例如 `scripting://run/F11eSync?serverUrl={url}`。
+pub fn pairing_payload(url: &str, template: Option<&str>) -> String {
+    match template.map(str::trim).filter(|t| !t.is_empty()) {
+        Some(t) => t.replace("{url}", &encode_query_component(url)),
+        None => url.to_string(),
+    }
+}
+
+/// 终端二维码（半高字符块）；颜色反转以适配深色终端。
+pub fn render_terminal_qr(payload: &str) -> Option<String> {
+    let code = QrCode::new(payload.as_bytes()).ok()?;
+    Some(
+        code.render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .quiet_zone(true)
+            .build(),
+    )
+}
@@ -0,0 +1,39 @@
+use std::path::Path;
+
+/// 把客户端/命令行给出的路径转换为同步目录下的相对路径（`/` 分隔、无首尾 `/`，根目录为空串）。
+/// 同步目录内的绝对路径会去掉前缀；目录外或含 `..` 时返回错误。纯计算，不检查路径是否存在。
+pub fn to_rel(base: &Path, raw: &str) -> Result<String, String> {
+    let raw = raw.trim();
+    let raw_path = Path::new(raw);
+    let rel = if raw_path.is_absolute() {
+        match raw_path.strip_prefix(base) {
+            Ok(rel) => rel.to_string_lossy().to_string(),
+            Err(_) => return Err(format!("路径不在同步目录内: {raw}")),
+        }
+    } else {
+        raw.to_string()
+    };
+    let rel = rel.replace('\\', "/");
+    let rel = rel.trim_start_matches("./").trim_matches('/');
+    if rel == "." {
+        return Ok(String::new());
+    }
+    if rel.split('/').any(|part| part == "..") {
+        return Err(format!("路径不在同步目录内: {raw}"));
+    }
+    Ok(rel.to_string())
+}
+
+/// 目录下子条目的相对路径。
+pub fn join_rel(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+/// 相对路径的各级上级目录（由浅到深，不含自身），如 `a/b/c` -> `["a", "a/b"]`。
+pub fn ancestors(rel: &str) -> Vec<&str> {
+    rel.match_indices('/').map(|(i, _)| &rel[..i]).collect()
+}
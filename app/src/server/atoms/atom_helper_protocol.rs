@@ -0,0 +1,61 @@
+use crate::proto::{ClientConfig, ClientHello, PeerCapabilities};
+
+/// 当前协议版本；未发送 `hello` 的旧版脚本视为协议 1
+pub const PROTOCOL_VERSION: u32 = 2;
+/// 发送 `hello` 的客户端最低协议版本
+pub const MIN_CLIENT_PROTOCOL: u32 = 2;
+/// 服务端支持的能力，握手时与客户端声明的能力取交集
+pub const SERVER_CAPABILITIES: &[&str] = &["utf8", "binary", "zstd", "gzip", "sync_path", "list_dir", "request_upload", "ack", "resume"];
+
+/// 握手被拒绝：`message` 说明原因，`upgrade_hint` 提示需要升级哪一端。
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    pub message: String,
+    pub upgrade_hint: String,
+}
+
+pub fn negotiate(hello: &ClientHello) -> Result<PeerCapabilities, Rejection> {
+    if hello.protocol < MIN_CLIENT_PROTOCOL {
+        return Err(Rejection {
+            message: format!("客户端协议版本 {} 过旧（服务端要求 ≥ {MIN_CLIENT_PROTOCOL}）", hello.protocol),
+            upgrade_hint: "请更新 Scripting 脚本到最新版本".to_string(),
+        });
+    }
+    let missing: Vec<&str> = hello
+        .required
+        .iter()
+        .map(String::as_str)
+        .filter(|cap| !SERVER_CAPABILITIES.contains(cap))
+        .collect();
+    if !missing.is_empty() {
+        return Err(Rejection {
+            message: format!("服务端不支持客户端要求的能力: {}", missing.join(", ")),
+            upgrade_hint: format!("请更新电脑端 F11eSync（当前版本 {}）", env!("CARGO_PKG_VERSION")),
+        });
+    }
+    let mut capabilities: Vec<String> = Vec::new();
+    for cap in hello.capabilities.iter().filter(|cap| SERVER_CAPABILITIES.contains(&cap.as_str())) {
+        if !capabilities.contains(cap) {
+            capabilities.push(cap.clone());
+        }
+    }
+    Ok(PeerCapabilities {
+        client_version: hello.clientVersion.clone(),
+        protocol: hello.protocol.min(PROTOCOL_VERSION),
+        capabilities,
+    })
+}
+
+/// 握手协商的编码/压缩能力并入 `configure` 的声明（已声明的保持原有顺序）。
+pub fn apply_codecs(peer: &PeerCapabilities, client: &mut ClientConfig) {
+    let add = |list: &mut Option<Vec<String>>, names: &[&str]| {
+        for name in names.iter().filter(|name| peer.has(name)) {
+            let list = list.get_or_insert_with(Vec::new);
+            if !list.iter().any(|e| e == name) {
+                list.push(name.to_string());
+            }
+        }
+    };
+    add(&mut client.encodings, &["utf8", "binary"]);
+    add(&mut client.compression, &["zstd", "gzip"]);
+}
@@ -0,0 +1,41 @@
+use super::atom_helper_pairing;
+use std::net::IpAddr;
+
+fn is_usable(ip: &IpAddr, allow_v6: bool) -> bool {
+    match ip {
+        IpAddr::V4(v4) => !v4.is_loopback() && !v4.is_link_local() && !v4.is_unspecified(),
+        // fe80::/10 需要 scope id，手机端无法直接访问
+        IpAddr::V6(v6) => allow_v6 && !v6.is_loopback() && (v6.segments()[0] & 0xffc0) != 0xfe80,
+    }
+}
+
+fn interface_ips(allow_v6: bool) -> Vec<IpAddr> {
+    let Ok(ifaces) = if_addrs::get_if_addrs() else {
+        return Vec::new();
+    };
+    let mut ips = ifaces
+        .into_iter()
+        .filter(|iface| !iface.is_loopback())
+        .map(|iface| iface.ip())
+        .filter(|ip| is_usable(ip, allow_v6))
+        .collect::<Vec<_>>();
+    ips.sort_by_key(|ip| (atom_helper_pairing::ip_rank(ip), *ip));
+    ips.dedup();
+    ips
+}
+
+/// 手机可访问的服务地址列表。监听 `0.0.0.0`/`::` 时枚举本机非回环网卡地址；
+/// 枚举失败或为空时退回监听地址本身。
+pub fn reachable_urls(host: IpAddr, port: u16) -> Vec<String> {
+    let ips = if host.is_unspecified() {
+        interface_ips(host.is_ipv6())
+    } else {
+        vec![host]
+    };
+    if ips.is_empty() {
+        return vec![atom_helper_pairing::format_http_url(host, port)];
+    }
+    ips.into_iter()
+        .map(|ip| atom_helper_pairing::format_http_url(ip, port))
+        .collect()
+}
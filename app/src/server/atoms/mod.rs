@@ -1,5 +1,12 @@
+pub mod atom_helper_encoding;
 pub mod atom_helper_filter;
 pub mod atom_helper_limits;
 pub mod atom_helper_messages;
+pub mod atom_helper_pairing;
+pub mod atom_helper_protocol;
+pub mod atom_helper_path;
+pub mod lan;
+pub mod snapshot;
 pub mod socket_emit;
 pub mod state;
+pub mod token;
@@ -0,0 +1,123 @@
+use super::atom_helper_limits;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+/// 单份快照的总大小上限；超过时放弃快照（通常是同步目录选错了）。
+pub const MAX_SNAPSHOT_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// 把同步目录中 `include(rel, is_dir, size)` 接受的条目复制到 `root/<目录名>-<unix 秒>[-n]`，
+/// 并只保留最近 `keep` 份；返回快照路径与文件数。`root` 位于同步目录内时跳过它自身。
+/// 阻塞 IO，调用方应放到 `spawn_blocking` 中执行。
+pub fn snapshot_dir(
+    src: &Path,
+    root: &Path,
+    keep: usize,
+    include: impl Fn(&str, bool, Option<u64>) -> bool,
+) -> Result<(PathBuf, u64)> {
+    std::fs::create_dir_all(root).with_context(|| format!("创建快照目录失败: {}", root.display()))?;
+    let root = std::fs::canonicalize(root)?;
+    let src = std::fs::canonicalize(src).with_context(|| format!("同步目录不可用: {}", src.display()))?;
+    let name = src
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "sync".to_string());
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let target = create_unique(&root, &format!("{name}-{secs}"))?;
+
+    let files = match copy_tree(&src, &root, &target, include) {
+        Ok(files) => files,
+        Err(err) => {
+            let _ = std::fs::remove_dir_all(&target);
+            return Err(err);
+        }
+    };
+
+    prune(&root, &format!("{name}-"), keep);
+    Ok((target, files))
+}
+
+/// 同一秒内的多次快照依次加 `-2`、`-3` 后缀；`create_dir` 保证不会复用已有目录。
+fn create_unique(root: &Path, base: &str) -> Result<PathBuf> {
+    for n in 1u32.. {
+        let candidate = match n {
+            1 => root.join(base),
+            _ => root.join(format!("{base}-{n}")),
+        };
+        match std::fs::create_dir(&candidate) {
+            Ok(()) => return Ok(candidate),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err).with_context(|| format!("创建快照目录失败: {}", candidate.display())),
+        }
+    }
+    unreachable!()
+}
+
+fn copy_tree(src: &Path, root: &Path, target: &Path, include: impl Fn(&str, bool, Option<u64>) -> bool) -> Result<u64> {
+    let mut files = 0u64;
+    let mut bytes = 0u64;
+    let mut it = WalkDir::new(src).into_iter();
+    while let Some(entry) = it.next() {
+        let Ok(entry) = entry else { continue };
+        if entry.path() == src {
+            continue;
+        }
+        let is_dir = entry.file_type().is_dir();
+        if entry.path().starts_with(root) {
+            if is_dir {
+                it.skip_current_dir();
+            }
+            continue;
+        }
+        let Some(rel) = crate::watcher::normalize_rel_path(src, entry.path()) else { continue };
+        if crate::watcher::should_ignore_rel(&rel) {
+            continue;
+        }
+        let size = (!is_dir).then(|| entry.metadata().ok().map(|m| m.len())).flatten();
+        if !include(&rel, is_dir, size) {
+            if is_dir {
+                it.skip_current_dir();
+            }
+            continue;
+        }
+
+        let dest = target.join(&rel);
+        if is_dir {
+            std::fs::create_dir_all(&dest).with_context(|| format!("创建快照目录失败: {}", dest.display()))?;
+        } else if entry.file_type().is_file() {
+            bytes += size.unwrap_or(0);
+            if bytes > MAX_SNAPSHOT_BYTES {
+                anyhow::bail!(
+                    "待快照内容超过上限 {}，已放弃（请确认同步目录是否选对）",
+                    atom_helper_limits::human_bytes(MAX_SNAPSHOT_BYTES)
+                );
+            }
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(entry.path(), &dest).with_context(|| format!("复制失败: {rel}"))?;
+            files += 1;
+        }
+    }
+    Ok(files)
+}
+
+fn prune(root: &Path, prefix: &str, keep: usize) {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return;
+    };
+    let mut snapshots = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let rest = name.strip_prefix(prefix)?;
+            let (secs, n) = rest.split_once('-').unwrap_or((rest, "1"));
+            Some(((secs.parse::<u64>().ok()?, n.parse::<u32>().ok()?), e.path()))
+        })
+        .collect::<Vec<_>>();
+    snapshots.sort_by_key(|(order, _)| std::cmp::Reverse(*order));
+    for (_, path) in snapshots.into_iter().skip(keep) {
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
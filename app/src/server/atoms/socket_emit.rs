@@ -1,12 +1,16 @@
-use super::atom_helper_messages;
-use super::state as state_atoms;
-use crate::proto::{ChunkAck, ChunkComplete, ChunkData, ChunkStart, DeletePayload, ServerLog, SyncControl};
+use super::atom_helper_encoding::EncodedContent;
+use super::{atom_helper_messages, atom_helper_protocol};
+use super::state::{self as state_atoms, LogFields};
+use crate::proto::{ChunkAck, ChunkComplete, ChunkData, ChunkStart, DeletePayload, DirListing, PeerCapabilities, ServerHello, ServerLog, SyncControl};
 use crate::server::RuntimeState;
 use anyhow::Result;
+use bytes::Bytes;
 use serde_json::Value;
 use tokio::sync::oneshot;
 use tokio::time::{timeout, Duration};
 
+const CHUNK_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub fn send_server_warning(state: &RuntimeState, title: String, reason: String) {
     let payload = ServerLog {
         action: "server_log".to_string(),
@@ -16,10 +20,11 @@ pub fn send_server_warning(state: &RuntimeState, title: String, reason: String)
         content: None,
     };
 
-    state_atoms::ui_log(
+    state_atoms::ui_log_with(
         state,
         "warn",
         atom_helper_messages::format_ts_warning_line(&reason, &title),
+        LogFields::new("server_log", &title),
     );
 
     if let Some(socket) = state.socket.lock().unwrap().as_ref() {
@@ -36,24 +41,41 @@ pub fn send_file_size_warning(state: &RuntimeState, path: String, reason: String
         content: None,
     };
 
-    state_atoms::ui_log(state, "warn", format!("文件过大，跳过 -> {path} ({reason})"));
+    state_atoms::ui_log_with(
+        state,
+        "warn",
+        format!("文件过大，跳过 -> {path} ({reason})"),
+        LogFields::new("server_log", &path),
+    );
 
     if let Some(socket) = state.socket.lock().unwrap().as_ref() {
         let _ = socket.emit("server_log", &payload);
     }
 }
 
-pub fn emit_update_small(state: &RuntimeState, rel: &str, b64: String) {
+pub fn emit_update_small(state: &RuntimeState, rel: &str, content: EncodedContent, size: u64) {
+    let encoding = content.encoding.name();
     let payload = serde_json::json!({
         "action": "update",
         "path": rel,
-        "content": b64,
+        "content": content.text,
         "isDir": false,
-        "encoding": "base64",
+        "encoding": encoding,
     });
     if let Some(socket) = state.socket.lock().unwrap().as_ref() {
-        let _ = socket.emit("update", &payload);
-        state_atoms::ui_log(state, "info", format!("广播: update -> {rel}"));
+        let _ = match content.binary {
+            Some(bytes) => socket.bin([bytes]).emit("update", &payload),
+            None => socket.emit("update", &payload),
+        };
+        state_atoms::ui_log_with(
+            state,
+            "info",
+            match content.encoding.compression {
+                Some(c) => format!("广播: update -> {rel}（{} 压缩）", c.as_str()),
+                None => format!("广播: update -> {rel}"),
+            },
+            LogFields::new("update", rel).bytes(size),
+        );
     }
 }
 
@@ -66,7 +88,12 @@ pub fn emit_create_dir(state: &RuntimeState, rel: &str) {
     });
     if let Some(socket) = state.socket.lock().unwrap().as_ref() {
         let _ = socket.emit("create_dir", &payload);
-        state_atoms::ui_log(state, "info", format!("广播: create_dir -> {rel}"));
+        state_atoms::ui_log_with(
+            state,
+            "info",
+            format!("广播: create_dir -> {rel}"),
+            LogFields::new("create_dir", rel),
+        );
     }
 }
 
@@ -79,22 +106,76 @@ pub fn emit_delete(state: &RuntimeState, rel: &str, is_dir: bool) {
     };
     if let Some(socket) = state.socket.lock().unwrap().as_ref() {
         let _ = socket.emit("delete", &payload);
-        state_atoms::ui_log(state, "info", format!("广播: delete -> {rel}"));
+        state_atoms::ui_log_with(
+            state,
+            "info",
+            format!("广播: delete -> {rel}"),
+            LogFields::new("delete", rel),
+        );
     }
 }
 
-pub fn emit_sync_control(state: &RuntimeState, action: &'static str) {
+pub fn emit_sync_control(state: &RuntimeState, action: &'static str, scope: &[String]) {
     if let Some(socket) = state.socket.lock().unwrap().as_ref() {
         let payload = SyncControl {
             action: action.to_string(),
             path: "".to_string(),
             content: None,
             isDir: false,
+            scope: (!scope.is_empty()).then(|| scope.to_vec()),
         };
         let _ = socket.emit(action, &payload);
     }
 }
 
+/// 请求客户端开始“上传全部”（客户端随后会发送 `client_upload_start`）。
+pub fn emit_request_upload(state: &RuntimeState) {
+    if let Some(socket) = state.socket.lock().unwrap().as_ref() {
+        let payload = SyncControl {
+            action: "request_upload".to_string(),
+            path: "".to_string(),
+            content: None,
+            isDir: false,
+            scope: None,
+        };
+        let _ = socket.emit("request_upload", &payload);
+    }
+}
+
+pub fn emit_dir_listing(state: &RuntimeState, listing: &DirListing) {
+    if let Some(socket) = state.socket.lock().unwrap().as_ref() {
+        let _ = socket.emit("dir_listing", listing);
+    }
+}
+
+pub fn emit_hello(state: &RuntimeState, peer: &PeerCapabilities, session_id: Option<String>, resumed: bool) {
+    if let Some(socket) = state.socket.lock().unwrap().as_ref() {
+        let payload = ServerHello {
+            serverVersion: env!("CARGO_PKG_VERSION").to_string(),
+            protocol: atom_helper_protocol::PROTOCOL_VERSION,
+            minProtocol: atom_helper_protocol::MIN_CLIENT_PROTOCOL,
+            capabilities: atom_helper_protocol::SERVER_CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+            negotiated: peer.capabilities.clone(),
+            sessionId: session_id,
+            resumed,
+        };
+        let _ = socket.emit("hello", &payload);
+    }
+}
+
+pub fn emit_fetch_error(state: &RuntimeState, rel: &str, msg: String) {
+    if let Some(socket) = state.socket.lock().unwrap().as_ref() {
+        let payload = SyncControl {
+            action: "fetch_error".to_string(),
+            path: rel.to_string(),
+            content: Some(msg),
+            isDir: false,
+            scope: None,
+        };
+        let _ = socket.emit("fetch_error", &payload);
+    }
+}
+
 pub fn emit_sync_error(state: &RuntimeState, msg: String) {
     if let Some(socket) = state.socket.lock().unwrap().as_ref() {
         let payload = SyncControl {
@@ -102,6 +183,7 @@ pub fn emit_sync_error(state: &RuntimeState, msg: String) {
             path: "".to_string(),
             content: Some(msg),
             isDir: false,
+            scope: None,
         };
         let _ = socket.emit("sync_error", &payload);
     }
@@ -125,23 +207,49 @@ pub fn emit_chunk_ack(state: &RuntimeState, ack: &ChunkAck) {
     }
 }
 
+/// 原生 ack 的回复：`{ success, error }`（与 `chunk_ack` 相同）或单个布尔值；其它内容视为成功。
+fn native_ack_success(data: Value) -> bool {
+    match state_atoms::extract_first_arg(data) {
+        Value::Bool(ok) => ok,
+        Value::Object(map) => map.get("success").and_then(Value::as_bool).unwrap_or(true),
+        _ => true,
+    }
+}
+
+/// 协商了 `ack` 能力时使用 Socket.IO 原生确认，否则等待客户端的 `chunk_ack` 事件。
 pub async fn send_chunk_and_wait_ack(
     state: &RuntimeState,
     file_id: &str,
     chunk_index: u32,
     payload: &ChunkData,
+    binary: Option<&Bytes>,
 ) -> Result<bool> {
     let Some(socket) = state.socket.lock().unwrap().as_ref().cloned() else {
         return Ok(false);
     };
 
+    if state_atoms::peer_supports(state, "ack") {
+        let op = socket.timeout(CHUNK_ACK_TIMEOUT);
+        let op = match binary {
+            Some(bytes) => op.bin([bytes.clone()]),
+            None => op,
+        };
+        let Ok(ack) = op.emit_with_ack::<_, Value>("chunk_data", payload) else {
+            return Ok(false);
+        };
+        return Ok(ack.await.is_ok_and(|res| native_ack_success(res.data)));
+    }
+
     let (tx, rx) = oneshot::channel::<bool>();
     let key = format!("{file_id}-{chunk_index}");
     state_atoms::insert_ack_waiter(state, key.clone(), tx);
 
-    socket.emit("chunk_data", payload).ok();
+    match binary {
+        Some(bytes) => socket.bin([bytes.clone()]).emit("chunk_data", payload).ok(),
+        None => socket.emit("chunk_data", payload).ok(),
+    };
 
-    let ok = timeout(Duration::from_secs(5), rx)
+    let ok = timeout(CHUNK_ACK_TIMEOUT, rx)
         .await
         .ok()
         .and_then(|r| r.ok())
@@ -1,9 +1,10 @@
-use super::atom_helper_filter;
-use crate::proto::ClientConfig;
-use regex::Regex;
+use super::{atom_helper_filter, atom_helper_protocol};
+use crate::config::ServerConfig;
+use crate::proto::{ChunkReceiveState, ClientConfig};
 use serde_json::Value;
 use socketioxide::extract::SocketRef;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use tokio::sync::oneshot;
 use tokio::time::Duration;
@@ -17,11 +18,50 @@ pub fn extract_first_arg(value: Value) -> Value {
     }
 }
 
+/// 结构化日志字段：随 `ui_log_with` 一并写入 tracing（JSON 格式下为独立字段）。
+#[derive(Debug, Clone, Default)]
+pub struct LogFields {
+    pub action: Option<&'static str>,
+    pub path: Option<String>,
+    pub bytes: Option<u64>,
+}
+
+impl LogFields {
+    pub fn new(action: &'static str, path: &str) -> Self {
+        Self {
+            action: Some(action),
+            path: Some(path.to_string()),
+            bytes: None,
+        }
+    }
+
+    pub fn bytes(mut self, bytes: u64) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+}
+
 pub fn ui_log(state: &RuntimeState, level: &'static str, message: impl Into<String>) {
-    let _ = state.ui_tx.send(UiEvent::Log {
-        level,
-        message: message.into(),
-    });
+    ui_log_with(state, level, message, LogFields::default());
+}
+
+pub fn ui_log_with(state: &RuntimeState, level: &'static str, message: impl Into<String>, fields: LogFields) {
+    let message = message.into();
+    let client = state.client_label.lock().unwrap().clone();
+    trace_ui_log(level, &message, &fields, client.as_deref());
+    let _ = state.ui_tx.send(UiEvent::Log { level, message });
+}
+
+fn trace_ui_log(level: &str, message: &str, fields: &LogFields, client: Option<&str>) {
+    let action = fields.action;
+    let path = fields.path.as_deref();
+    let bytes = fields.bytes;
+    match level {
+        "error" => tracing::error!(target: "f11esync::sync", action, path, bytes, client, "{message}"),
+        "warn" => tracing::warn!(target: "f11esync::sync", action, path, bytes, client, "{message}"),
+        "debug" => tracing::debug!(target: "f11esync::sync", action, path, bytes, client, "{message}"),
+        _ => tracing::info!(target: "f11esync::sync", action, path, bytes, client, "{message}"),
+    }
 }
 
 pub fn set_socket_if_empty(state: &RuntimeState, socket: SocketRef) -> bool {
@@ -29,33 +69,158 @@ pub fn set_socket_if_empty(state: &RuntimeState, socket: SocketRef) -> bool {
     if guard.is_some() {
         return false;
     }
+    *state.client_label.lock().unwrap() = Some(socket.id.to_string());
     *guard = Some(socket);
+    state.connection_gen.fetch_add(1, Ordering::SeqCst);
     true
 }
 
+/// 取出当前客户端 socket（用于替换/断开客户端）。
+pub fn take_socket(state: &RuntimeState) -> Option<SocketRef> {
+    let socket = {
+        let mut guard = state.socket.lock().unwrap();
+        state.connection_gen.fetch_add(1, Ordering::SeqCst);
+        guard.take()
+    };
+    *state.client_label.lock().unwrap() = None;
+    socket
+}
+
+/// 当前连接代号；发送循环开始时记录，之后用 `is_same_connection` 判断是否应停止。
+pub fn connection_generation(state: &RuntimeState) -> u64 {
+    state.connection_gen.load(Ordering::SeqCst)
+}
+
+/// 客户端仍连接，且与 `generation` 记录时是同一个连接（未被替换/断开后重连）。
+pub fn is_same_connection(state: &RuntimeState, generation: u64) -> bool {
+    let guard = state.socket.lock().unwrap();
+    guard.is_some() && connection_generation(state) == generation
+}
+
+/// 客户端声明设备身份后，日志中的 `client` 字段改用设备名。
+pub fn identify_client(state: &RuntimeState, label: &str) {
+    *state.client_label.lock().unwrap() = Some(label.to_string());
+    let _ = state.ui_tx.send(UiEvent::ClientIdentified(label.to_string()));
+}
+
+pub fn is_current_socket(state: &RuntimeState, socket: &SocketRef) -> bool {
+    state.socket.lock().unwrap().as_ref().is_some_and(|s| s.id == socket.id)
+}
+
 pub fn clear_socket(state: &RuntimeState) {
-    *state.socket.lock().unwrap() = None;
+    {
+        let mut guard = state.socket.lock().unwrap();
+        state.connection_gen.fetch_add(1, Ordering::SeqCst);
+        *guard = None;
+    }
+    *state.client_label.lock().unwrap() = None;
 }
 
 pub fn reset_connection_state(state: &RuntimeState) {
-    state.chunk_receive_state.lock().unwrap().clear();
+    remove_chunk_temp_files(state.chunk_receive_state.lock().unwrap().drain().map(|(_, st)| st));
     state.chunk_ack_waiters.lock().unwrap().clear();
     state.server_written.lock().unwrap().clear();
     state.client_written.lock().unwrap().clear();
     *state.client_config.lock().unwrap() = ClientConfig::default();
-    *state.effective_regex.lock().unwrap() = state.server_side_regex.as_ref().clone();
+    *state.peer.lock().unwrap() = None;
+    *state.device.lock().unwrap() = None;
+    *state.session_id.lock().unwrap() = None;
+    *state.effective_regex.lock().unwrap() = state.server_side_regex.lock().unwrap().clone();
+    state.tree_size_dirty.store(true, std::sync::atomic::Ordering::SeqCst);
 }
 
-pub fn rebuild_effective_regex(state: &RuntimeState, client: &ClientConfig) {
-    let mut merged: Vec<Regex> = state.server_side_regex.as_ref().clone();
-    if let Some(list) = &client.pathRegex {
-        for s in list {
-            if let Ok(re) = Regex::new(s) {
-                merged.push(re);
-            }
+/// 独占标志：`try_set` 成功后持有，任务结束（包括 panic）时清除。
+pub struct FlagGuard(Arc<AtomicBool>);
+
+impl FlagGuard {
+    pub fn try_set(flag: &Arc<AtomicBool>) -> Option<Self> {
+        (!flag.swap(true, Ordering::SeqCst)).then(|| Self(flag.clone()))
+    }
+}
+
+impl Drop for FlagGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// 删除未完成的压缩上传留下的临时文件（分片直接写入目标文件的不处理）。
+pub fn remove_chunk_temp_files(states: impl IntoIterator<Item = ChunkReceiveState>) {
+    for st in states {
+        if st.write_path != st.abs_path {
+            let _ = std::fs::remove_file(&st.write_path);
         }
     }
-    *state.effective_regex.lock().unwrap() = merged;
+}
+
+pub fn peer_supports(state: &RuntimeState, capability: &str) -> bool {
+    state.peer.lock().unwrap().as_ref().is_some_and(|peer| peer.has(capability))
+}
+
+/// 发送文件时使用的客户端配置：`configure` 声明合并握手协商的编码/压缩能力。
+pub fn codec_client_config(state: &RuntimeState) -> ClientConfig {
+    let mut client = state.client_config.lock().unwrap().clone();
+    if let Some(peer) = state.peer.lock().unwrap().as_ref() {
+        atom_helper_protocol::apply_codecs(peer, &mut client);
+    }
+    client
+}
+
+pub fn rebuild_effective_regex(state: &RuntimeState, client: &ClientConfig) {
+    state.tree_size_dirty.store(true, std::sync::atomic::Ordering::SeqCst);
+    let server = state.server_side_regex.lock().unwrap().clone();
+    *state.effective_regex.lock().unwrap() = atom_helper_filter::effective_regexes(&server, client.pathRegex.as_deref());
+}
+
+pub fn server_config(state: &RuntimeState) -> ServerConfig {
+    state.cfg.lock().unwrap().clone()
+}
+
+/// 运行中热更新：过滤规则与大小限制立即生效（不断开客户端，正在进行的传输按下一个文件起生效）；
+/// 监听地址与同步目录只记录，需重启服务。
+pub fn apply_server_config(state: &RuntimeState, next: ServerConfig) {
+    if let Some((n, pattern, err)) = atom_helper_filter::invalid_regexes(&next.server_side_path_regex).first() {
+        ui_log(state, "warn", format!("过滤规则第 {n} 条 `{pattern}` 不合法，未应用新配置: {err}"));
+        return;
+    }
+    let prev = std::mem::replace(&mut *state.cfg.lock().unwrap(), next.clone());
+    state.tree_size_dirty.store(true, std::sync::atomic::Ordering::SeqCst);
+
+    if (prev.host, prev.port, &prev.dir) != (next.host, next.port, &next.dir) {
+        ui_log(state, "warn", "监听地址/端口/同步目录的修改需重启服务后生效");
+    }
+
+    let mut changed = Vec::new();
+    if prev.server_side_path_regex != next.server_side_path_regex {
+        *state.server_side_regex.lock().unwrap() = atom_helper_filter::compile_regexes(&next.server_side_path_regex);
+        let client = state.client_config.lock().unwrap().clone();
+        rebuild_effective_regex(state, &client);
+        changed.push("过滤规则");
+    }
+    if prev.enable_server_side_file_size_limit != next.enable_server_side_file_size_limit
+        || prev.max_server_side_file_size != next.max_server_side_file_size
+        || prev.size_rules != next.size_rules
+    {
+        changed.push("文件大小限制");
+    }
+    if prev.require_device_approval != next.require_device_approval || prev.trusted_devices != next.trusted_devices {
+        changed.push("设备确认");
+    }
+    // GUI 中修改了当前设备的规则时立即生效
+    let device_id = state.device.lock().unwrap().as_ref().map(|d| d.id.clone());
+    if let Some(rules) = device_id.as_ref().and_then(|id| next.device_rules.get(id).filter(|r| prev.device_rules.get(id) != Some(*r))) {
+        let merged = {
+            let mut guard = state.client_config.lock().unwrap();
+            rules.apply_to(&mut guard);
+            guard.clone()
+        };
+        rebuild_effective_regex(state, &merged);
+        let _ = state.ui_tx.send(UiEvent::ClientConfigured(merged));
+        changed.push("当前设备规则");
+    }
+    if !changed.is_empty() {
+        ui_log(state, "info", format!("已应用新配置: {}", changed.join("、")));
+    }
 }
 
 pub fn merge_client_config(base: &mut ClientConfig, incoming: ClientConfig) {
@@ -68,6 +233,12 @@ pub fn merge_client_config(base: &mut ClientConfig, incoming: ClientConfig) {
     if incoming.pathRegex.is_some() {
         base.pathRegex = incoming.pathRegex;
     }
+    if incoming.encodings.is_some() {
+        base.encodings = incoming.encodings;
+    }
+    if incoming.compression.is_some() {
+        base.compression = incoming.compression;
+    }
 }
 
 pub fn should_filter_rel(state: &RuntimeState, rel: &str) -> bool {
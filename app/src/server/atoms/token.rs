@@ -0,0 +1,8 @@
+use anyhow::{Context, Result};
+
+/// 随机口令（64 个十六进制字符），取自操作系统的安全随机源；随机源不可用时返回错误。
+pub fn random_token() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).context("读取系统随机数失败")?;
+    Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+}
@@ -10,81 +10,224 @@ use socketioxide::extract::SocketRef;
 use socketioxide::SocketIo;
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use tokio::sync::{mpsc, oneshot};
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{error, info, warn};
 
-use crate::config::ServerConfig;
-use crate::proto::{ChunkReceiveState, ClientConfig};
-use self::molecules::fs_broadcast;
+use crate::config::{DeviceRules, KnownDevice, ServerConfig};
+use crate::proto::{ChunkReceiveState, ClientConfig, PeerCapabilities, SuspendedSession, UploadSession};
+use self::atoms::{atom_helper_filter, atom_helper_pairing, lan, state as state_atoms};
+use self::molecules::{client_takeover, device_approval, fs_broadcast, http_api, sync_all, tree_quota, upload_request};
 
 #[derive(Debug, Clone)]
 #[cfg_attr(not(feature = "gui"), allow(dead_code))]
 pub enum UiEvent {
     Log { level: &'static str, message: String },
-    Running { addr: SocketAddr },
+    Running { addr: SocketAddr, urls: Vec<String> },
     Stopped,
-    ClientConnected,
+    /// 设备名（User-Agent + socket id）
+    ClientConnected(String),
+    /// 客户端在 `hello` 中声明了设备身份（`KnownDevice` 的显示名）
+    ClientIdentified(String),
     ClientDisconnected,
+    ClientConfigured(ClientConfig),
+    /// 询问模式下有新客户端等待确认
+    TakeoverRequested(String),
+    /// 等待确认的连接已被允许/拒绝/超时/断开
+    TakeoverResolved,
+    /// 开启设备确认时有未知设备等待确认
+    DeviceApprovalRequested(String),
+    /// 等待确认的设备已被允许/拒绝/超时/断开
+    DeviceApprovalResolved,
+    /// 设备已加入信任列表（配置文件已写入）
+    DeviceTrusted(KnownDevice),
+    /// 某台设备的客户端规则已更新并写入配置文件
+    DeviceRulesStored { id: String, rules: DeviceRules },
+    SyncProgress { done: u32, total: u32 },
+    /// 同步目录中会发送的文件数与总字节数（按实时规则统计）
+    TreeSize { files: u64, bytes: u64, over_quota: bool },
+}
+
+/// 运行中服务的控制指令（与 shutdown 通道并列，由 GUI 等宿主持有发送端）。
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub enum ServerCommand {
+    Reconfigure(ServerConfig),
+    /// 服务端主动推送（与客户端 `sync_all`/`sync_path` 同一流程）；空列表表示整个同步目录
+    Push(Vec<String>),
+    /// 请求客户端“上传全部”（先为同步目录拍快照）
+    RequestUpload,
+    /// 断开当前客户端（不保留会话）
+    DisconnectClient,
+    /// 允许（true）或拒绝等待确认的新连接
+    ResolveTakeover(bool),
+    /// 允许或拒绝等待确认的未知设备；`remember` 为 true 时加入信任列表
+    ResolveDevice { accept: bool, remember: bool },
 }
 
 #[derive(Clone)]
 pub(crate) struct RuntimeState {
-    pub(crate) cfg: ServerConfig,
+    pub(crate) dir: PathBuf,
+    pub(crate) cfg: Arc<Mutex<ServerConfig>>,
     pub(crate) socket: Arc<Mutex<Option<SocketRef>>>,
+    /// 连接代号：每次设置/替换/清除 `socket` 时递增，发送循环据此发现连接已更换
+    pub(crate) connection_gen: Arc<AtomicU64>,
+    pub(crate) client_label: Arc<Mutex<Option<String>>>,
     pub(crate) client_config: Arc<Mutex<ClientConfig>>,
+    pub(crate) peer: Arc<Mutex<Option<PeerCapabilities>>>,
+    /// 当前客户端在 `hello` 中声明的设备身份
+    pub(crate) device: Arc<Mutex<Option<KnownDevice>>>,
+    pub(crate) session_id: Arc<Mutex<Option<String>>>,
+    pub(crate) suspended: Arc<Mutex<Option<SuspendedSession>>>,
+    pub(crate) pending_takeover: Arc<Mutex<Option<SocketRef>>>,
+    pub(crate) pending_device: Arc<Mutex<Option<(SocketRef, KnownDevice)>>>,
+    /// 经电脑端允许的连接（socket id）与其设备 id，只对该连接有效
+    pub(crate) approved_device: Arc<Mutex<Option<(String, String)>>>,
     pub(crate) server_written: Arc<Mutex<HashMap<String, u64>>>,
     pub(crate) client_written: Arc<Mutex<HashMap<String, u64>>>,
     pub(crate) chunk_receive_state: Arc<Mutex<HashMap<String, ChunkReceiveState>>>,
     pub(crate) chunk_ack_waiters: Arc<Mutex<HashMap<String, oneshot::Sender<bool>>>>,
     pub(crate) ui_tx: mpsc::UnboundedSender<UiEvent>,
-    pub(crate) server_side_regex: Arc<Vec<Regex>>,
+    pub(crate) server_side_regex: Arc<Mutex<Vec<Regex>>>,
     pub(crate) effective_regex: Arc<Mutex<Vec<Regex>>>,
+    pub(crate) sync_running: Arc<AtomicBool>,
+    /// 目录或规则有变化，需要重新统计总量
+    pub(crate) tree_size_dirty: Arc<AtomicBool>,
+    pub(crate) upload_session: Arc<Mutex<Option<UploadSession>>>,
+    pub(crate) pending_snapshot: Arc<Mutex<Option<PathBuf>>>,
+    /// 服务端发起的上传请求正在拍快照或等待客户端响应
+    pub(crate) upload_requesting: Arc<AtomicBool>,
+    /// HTTP 控制接口（`/api/*`）的口令
+    pub(crate) control_token: Arc<str>,
+}
+
+impl RuntimeState {
+    pub(crate) fn new(
+        cfg: &ServerConfig,
+        ui_tx: mpsc::UnboundedSender<UiEvent>,
+        server_side_regex: Vec<Regex>,
+        control_token: String,
+    ) -> Self {
+        Self {
+            dir: cfg.dir.clone(),
+            cfg: Arc::new(Mutex::new(cfg.clone())),
+            socket: Arc::new(Mutex::new(None)),
+            connection_gen: Arc::new(AtomicU64::new(0)),
+            client_label: Arc::new(Mutex::new(None)),
+            client_config: Arc::new(Mutex::new(ClientConfig::default())),
+            peer: Arc::new(Mutex::new(None)),
+            device: Arc::new(Mutex::new(None)),
+            session_id: Arc::new(Mutex::new(None)),
+            suspended: Arc::new(Mutex::new(None)),
+            pending_takeover: Arc::new(Mutex::new(None)),
+            pending_device: Arc::new(Mutex::new(None)),
+            approved_device: Arc::new(Mutex::new(None)),
+            server_written: Arc::new(Mutex::new(HashMap::new())),
+            client_written: Arc::new(Mutex::new(HashMap::new())),
+            chunk_receive_state: Arc::new(Mutex::new(HashMap::new())),
+            chunk_ack_waiters: Arc::new(Mutex::new(HashMap::new())),
+            ui_tx,
+            effective_regex: Arc::new(Mutex::new(server_side_regex.clone())),
+            server_side_regex: Arc::new(Mutex::new(server_side_regex)),
+            sync_running: Arc::new(AtomicBool::new(false)),
+            tree_size_dirty: Arc::new(AtomicBool::new(true)),
+            upload_session: Arc::new(Mutex::new(None)),
+            pending_snapshot: Arc::new(Mutex::new(None)),
+            upload_requesting: Arc::new(AtomicBool::new(false)),
+            control_token: control_token.into(),
+        }
+    }
 }
 
+#[cfg_attr(feature = "gui", allow(dead_code))]
 pub async fn run_headless(cfg: ServerConfig) -> Result<()> {
+    print_pairing_qr(&cfg);
     let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
     tokio::spawn(async move {
         let _ = tokio::signal::ctrl_c().await;
         let _ = shutdown_tx.send(());
     });
-    run_server(cfg, shutdown_rx, None).await
+    let (cmd_tx, cmd_rx) = mpsc::unbounded_channel::<ServerCommand>();
+    println!("输入 push [路径...] 回车可把全部文件（或指定子路径）推送到已连接的手机，输入 pull 回车请求手机上传全部文件，kick 断开当前客户端，accept/reject 允许或拒绝等待确认的新连接，approve/trust/deny 处理等待确认的设备");
+    tokio::spawn(read_stdin_commands(cmd_tx));
+    run_server(cfg, shutdown_rx, None, Some(cmd_rx)).await
+}
+
+/// 无界面模式的控制通道：`push [路径...]` 推送全部文件或指定子路径到手机，`pull` 请求手机上传全部文件，
+/// `kick` 断开当前客户端，`accept`/`reject` 处理等待确认的新连接，
+/// `approve`（仅本次）/`trust`（并加入信任列表）/`deny` 处理等待确认的未知设备。
+async fn read_stdin_commands(tx: mpsc::UnboundedSender<ServerCommand>) {
+    use tokio::io::AsyncBufReadExt;
+
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let mut words = line.split_whitespace();
+        match words.next().unwrap_or_default() {
+            "" => {}
+            "push" | "p" => {
+                let _ = tx.send(ServerCommand::Push(words.map(str::to_string).collect()));
+            }
+            "pull" => {
+                let _ = tx.send(ServerCommand::RequestUpload);
+            }
+            "kick" => {
+                let _ = tx.send(ServerCommand::DisconnectClient);
+            }
+            cmd @ ("accept" | "reject") => {
+                let _ = tx.send(ServerCommand::ResolveTakeover(cmd == "accept"));
+            }
+            cmd @ ("approve" | "trust" | "deny") => {
+                let _ = tx.send(ServerCommand::ResolveDevice { accept: cmd != "deny", remember: cmd == "trust" });
+            }
+            other => warn!("未知命令: {other}（可用: push, pull, kick, accept, reject, approve, trust, deny）"),
+        }
+    }
+}
+
+fn print_pairing_qr(cfg: &ServerConfig) {
+    let urls = lan::reachable_urls(cfg.host, cfg.port);
+    let Some(url) = urls.first() else { return };
+    let payload = atom_helper_pairing::pairing_payload(url, cfg.pair_url_template.as_deref());
+    if let Some(qr) = atom_helper_pairing::render_terminal_qr(&payload) {
+        println!("{qr}");
+        println!("扫码连接: {payload}");
+    }
 }
 
 pub async fn run_server(
     cfg: ServerConfig,
     mut shutdown: oneshot::Receiver<()>,
     ui_tx: Option<mpsc::UnboundedSender<UiEvent>>,
+    commands: Option<mpsc::UnboundedReceiver<ServerCommand>>,
 ) -> Result<()> {
     tokio::fs::create_dir_all(&cfg.dir)
         .await
         .with_context(|| format!("创建同步目录失败: {}", cfg.dir.display()))?;
 
+    // 有界面时 GUI 显示同步目录总量，无界面时只在设置了配额时统计
+    let show_tree_size = ui_tx.is_some();
     let ui_tx = ui_tx.unwrap_or_else(|| {
         let (tx, _rx) = mpsc::unbounded_channel::<UiEvent>();
         tx
     });
 
-    let server_side_regex = cfg
-        .server_side_path_regex
-        .iter()
-        .filter_map(|s| Regex::new(s).ok())
-        .collect::<Vec<_>>();
-
-    let state = RuntimeState {
-        cfg: cfg.clone(),
-        socket: Arc::new(Mutex::new(None)),
-        client_config: Arc::new(Mutex::new(ClientConfig::default())),
-        server_written: Arc::new(Mutex::new(HashMap::new())),
-        client_written: Arc::new(Mutex::new(HashMap::new())),
-        chunk_receive_state: Arc::new(Mutex::new(HashMap::new())),
-        chunk_ack_waiters: Arc::new(Mutex::new(HashMap::new())),
-        ui_tx: ui_tx.clone(),
-        server_side_regex: Arc::new(server_side_regex),
-        effective_regex: Arc::new(Mutex::new(Vec::new())),
+    let issues = atom_helper_filter::invalid_regexes(&cfg.server_side_path_regex);
+    if let Some((n, pattern, err)) = issues.first() {
+        anyhow::bail!("过滤规则第 {n} 条 `{pattern}` 不合法: {err}");
+    }
+    let server_side_regex = atom_helper_filter::compile_regexes(&cfg.server_side_path_regex);
+    let control_token = match crate::settings::load_or_create_control_token() {
+        Ok(token) => token,
+        Err(err) => {
+            warn!("{err:#}；本次使用临时口令，命令行 push/pull 不可用");
+            atoms::token::random_token()?
+        }
     };
-    *state.effective_regex.lock().unwrap() = state.server_side_regex.as_ref().clone();
+
+    let state = RuntimeState::new(&cfg, ui_tx.clone(), server_side_regex, control_token);
 
     let (layer, io) = SocketIo::builder().with_state(state.clone()).build_layer();
     io.ns("/", self::molecules::socket_handlers::on_connect);
@@ -94,9 +237,11 @@ pub async fn run_server(
         .allow_methods(Any)
         .allow_headers(Any);
 
+    // 控制接口不加跨域头：浏览器中的网页无法携带口令调用
     let app = Router::new()
         .route("/", get(|| async { "F11eSync (Rust) is running" }))
         .layer(cors)
+        .merge(http_api::routes(state.clone()))
         .layer(layer);
 
     let addr = SocketAddr::new(cfg.host, cfg.port);
@@ -105,23 +250,66 @@ pub async fn run_server(
         level: "info",
         message: format!("监听地址: http://{}:{}", cfg.host, cfg.port),
     });
-    let _ = ui_tx.send(UiEvent::Running { addr });
+    let urls = lan::reachable_urls(cfg.host, cfg.port);
+    for url in &urls {
+        info!("可访问地址: {url}");
+        let _ = ui_tx.send(UiEvent::Log {
+            level: "info",
+            message: format!("可访问地址: {url}"),
+        });
+    }
+    let _ = ui_tx.send(UiEvent::Running { addr, urls });
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
 
     let (_watcher, mut fs_rx) = crate::watcher::start_watcher(&cfg)?;
+    let tree_monitor = tree_quota::spawn_monitor(&state, show_tree_size);
     let state_for_fs = state.clone();
     tokio::spawn(async move {
         while let Some(ev) = fs_rx.recv().await {
-            if state_for_fs.socket.lock().unwrap().is_none() {
-                continue;
-            }
+            state_for_fs.tree_size_dirty.store(true, Ordering::SeqCst);
             if let Err(err) = fs_broadcast::handle_fs_event(&state_for_fs, ev).await {
                 warn!("处理文件事件失败: {err:#}");
             }
         }
     });
 
+    if let Some(mut commands) = commands {
+        let state_for_cmd = state.clone();
+        tokio::spawn(async move {
+            while let Some(cmd) = commands.recv().await {
+                match cmd {
+                    ServerCommand::Reconfigure(next) => state_atoms::apply_server_config(&state_for_cmd, next),
+                    ServerCommand::Push(paths) => {
+                        if let Err(reason) = sync_all::start(&state_for_cmd, &paths, "服务端推送") {
+                            state_atoms::ui_log(&state_for_cmd, "warn", format!("无法推送: {reason}"));
+                        }
+                    }
+                    ServerCommand::RequestUpload => {
+                        if let Err(reason) = upload_request::request(&state_for_cmd) {
+                            state_atoms::ui_log(&state_for_cmd, "warn", format!("无法请求客户端上传: {reason}"));
+                        }
+                    }
+                    ServerCommand::DisconnectClient => {
+                        if let Err(reason) = client_takeover::disconnect_client(&state_for_cmd) {
+                            state_atoms::ui_log(&state_for_cmd, "warn", format!("无法断开客户端: {reason}"));
+                        }
+                    }
+                    ServerCommand::ResolveTakeover(accept) => {
+                        if let Err(reason) = client_takeover::resolve(&state_for_cmd, accept) {
+                            state_atoms::ui_log(&state_for_cmd, "warn", format!("无法处理新连接: {reason}"));
+                        }
+                    }
+                    ServerCommand::ResolveDevice { accept, remember } => {
+                        if let Err(reason) = device_approval::resolve(&state_for_cmd, accept, remember) {
+                            state_atoms::ui_log(&state_for_cmd, "warn", format!("无法处理设备确认: {reason}"));
+                        }
+                    }
+                }
+            }
+        });
+    }
+
     tokio::select! {
         res = axum::serve(listener, app) => {
             if let Err(err) = res {
@@ -133,6 +321,7 @@ pub async fn run_server(
         }
     }
 
+    tree_monitor.abort();
     if let Some(socket) = state.socket.lock().unwrap().take() {
         let _ = socket.disconnect();
     }
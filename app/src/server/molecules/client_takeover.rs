@@ -0,0 +1,139 @@
+use crate::config::TakeoverPolicy;
+use crate::proto::ConnectionRejected;
+use crate::server::atoms::state as state_atoms;
+use crate::server::molecules::{socket_handlers, upload_request};
+use crate::server::{RuntimeState, UiEvent};
+use socketioxide::extract::{SocketRef, State};
+use std::time::Duration;
+
+/// 询问模式下等待确认的时长，超时按拒绝处理
+const ASK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 用于日志/GUI 的设备名：User-Agent + socket id。
+pub fn device_label(socket: &SocketRef) -> String {
+    let ua = socket
+        .req_parts()
+        .headers
+        .get(axum::http::header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("未知设备");
+    format!("{ua} ({})", socket.id)
+}
+
+/// 向指定 socket 发送 `connection_rejected` 并断开（客户端收到后不再自动重连）。
+pub fn reject(socket: SocketRef, message: impl Into<String>) {
+    let payload = ConnectionRejected {
+        message: message.into(),
+        upgradeHint: None,
+    };
+    socket.emit("connection_rejected", &payload).ok();
+    socket.disconnect().ok();
+}
+
+/// 已有客户端连接时按 `takeover` 策略处理新连接。
+pub fn on_busy(state: &RuntimeState, socket: SocketRef) {
+    match state_atoms::server_config(state).takeover {
+        TakeoverPolicy::Reject => reject(socket, "连接失败：已有其他客户端连接，不允许多个客户端同时连接"),
+        TakeoverPolicy::ReplaceOldest => {
+            state_atoms::ui_log(state, "warn", format!("新客户端 {} 连接，替换旧连接", device_label(&socket)));
+            replace(state, socket);
+        }
+        TakeoverPolicy::Ask => ask(state, socket),
+    }
+}
+
+/// 断开当前客户端（不保留会话），被断开的客户端收到 `reason`。
+pub fn evict(state: &RuntimeState, old: SocketRef, reason: &str) {
+    upload_request::on_disconnect(state);
+    state_atoms::reset_connection_state(state);
+    let _ = state.ui_tx.send(UiEvent::ClientDisconnected);
+    reject(old, reason);
+}
+
+/// GUI/stdin 的“断开客户端”。
+pub fn disconnect_client(state: &RuntimeState) -> Result<(), &'static str> {
+    let old = state_atoms::take_socket(state).ok_or("客户端未连接")?;
+    state_atoms::ui_log(state, "warn", format!("已断开客户端 {}", device_label(&old)));
+    evict(state, old, "已被电脑端断开连接");
+    Ok(())
+}
+
+fn replace(state: &RuntimeState, socket: SocketRef) {
+    if let Some(old) = state_atoms::take_socket(state) {
+        evict(state, old, "已有新的客户端连接，当前连接被替换");
+    }
+    if state_atoms::set_socket_if_empty(state, socket.clone()) {
+        socket_handlers::accept(state, socket);
+    } else {
+        reject(socket, "连接失败：已有其他客户端连接，不允许多个客户端同时连接");
+    }
+}
+
+fn ask(state: &RuntimeState, socket: SocketRef) {
+    {
+        let mut pending = state.pending_takeover.lock().unwrap();
+        if pending.is_some() {
+            drop(pending);
+            reject(socket, "连接失败：已有其他连接在等待电脑端确认");
+            return;
+        }
+        *pending = Some(socket.clone());
+    }
+
+    socket.on_disconnect(|socket: SocketRef, State(state): State<RuntimeState>| {
+        if take_pending_if(state, &socket).is_some() {
+            state_atoms::ui_log(state, "info", "等待确认的客户端已断开");
+            let _ = state.ui_tx.send(UiEvent::TakeoverResolved);
+        }
+    });
+
+    let device = device_label(&socket);
+    let payload = serde_json::json!({
+        "action": "takeover_pending",
+        "message": "已有其他客户端连接，等待电脑端确认…",
+    });
+    socket.emit("takeover_pending", &payload).ok();
+    state_atoms::ui_log(
+        state,
+        "warn",
+        format!("新客户端 {device} 请求连接，等待确认（GUI 中允许/拒绝，或输入 accept/reject）"),
+    );
+    let _ = state.ui_tx.send(UiEvent::TakeoverRequested(device));
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(ASK_TIMEOUT).await;
+        if let Some(socket) = take_pending_if(&state, &socket) {
+            state_atoms::ui_log(&state, "warn", "新客户端等待确认超时，已拒绝");
+            let _ = state.ui_tx.send(UiEvent::TakeoverResolved);
+            reject(socket, "连接失败：电脑端未确认新的连接");
+        }
+    });
+}
+
+/// 处理等待确认的连接：允许时断开当前客户端并接受新连接，否则拒绝新连接。
+pub fn resolve(state: &RuntimeState, accept: bool) -> Result<(), &'static str> {
+    let socket = state.pending_takeover.lock().unwrap().take().ok_or("没有等待确认的连接")?;
+    let _ = state.ui_tx.send(UiEvent::TakeoverResolved);
+    if !accept {
+        state_atoms::ui_log(state, "info", format!("已拒绝新客户端 {}", device_label(&socket)));
+        reject(socket, "连接失败：电脑端拒绝了新的连接");
+        return Ok(());
+    }
+    if !socket.connected() {
+        return Err("等待确认的客户端已断开");
+    }
+    state_atoms::ui_log(state, "warn", format!("已允许新客户端 {}，替换旧连接", device_label(&socket)));
+    replace(state, socket.clone());
+    let payload = serde_json::json!({ "action": "takeover_accepted" });
+    socket.emit("takeover_accepted", &payload).ok();
+    Ok(())
+}
+
+fn take_pending_if(state: &RuntimeState, socket: &SocketRef) -> Option<SocketRef> {
+    let mut pending = state.pending_takeover.lock().unwrap();
+    if pending.as_ref().is_some_and(|s| s.id == socket.id) {
+        return pending.take();
+    }
+    None
+}
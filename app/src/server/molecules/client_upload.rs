@@ -1,36 +1,60 @@
 use crate::proto::{ChunkAck, ChunkComplete, ChunkData, ChunkReceiveState, ChunkStart, CreateDir, UpdateFile};
-use crate::server::atoms::{atom_helper_limits, socket_emit, state as state_atoms};
+use crate::server::atoms::state::{self as state_atoms, LogFields};
+use crate::server::atoms::atom_helper_encoding::{Compression, WireEncoding};
+use crate::server::atoms::{atom_helper_limits, socket_emit};
+use crate::server::molecules::{session_resume, upload_request};
 use crate::server::RuntimeState;
-use base64::Engine;
+use std::path::{Path, PathBuf};
 use tracing::error;
 
-pub async fn handle_update(state: &RuntimeState, data: UpdateFile) {
-    if data.encoding.as_deref() != Some("base64") {
-        return;
-    }
+pub async fn handle_update(state: &RuntimeState, data: UpdateFile, attachment: Option<&[u8]>) {
     let rel = data.path.replace('\\', "/");
     if crate::watcher::should_ignore_rel(&rel) {
         return;
     }
+    let encoding = match WireEncoding::parse(data.encoding.as_deref()) {
+        Ok(encoding) => encoding,
+        Err(reason) => {
+            socket_emit::send_server_warning(state, rel, reason);
+            return;
+        }
+    };
+    let client = state.client_config.lock().unwrap().clone();
+    let cfg = state_atoms::server_config(state);
+    // 未压缩的内容在解码前按解码后大小检查；压缩内容解压时不超过生效的限制
+    if encoding.compression.is_none() {
+        let size = encoding.transport.decoded_len(&data.content, attachment);
+        if let Some(reason) = atom_helper_limits::validate_file_size(&rel, size, &client, &cfg) {
+            socket_emit::send_file_size_warning(state, rel, reason);
+            return;
+        }
+    }
+    let limit = atom_helper_limits::decompress_limit(&rel, &client, &cfg);
+    let bytes = match encoding.decode(&data.content, attachment, limit) {
+        Ok(bytes) => bytes,
+        Err(reason) => {
+            socket_emit::send_server_warning(state, rel, reason);
+            return;
+        }
+    };
 
-    let abs = state.cfg.dir.join(&rel);
+    let abs = state.dir.join(&rel);
     if let Some(parent) = abs.parent() {
         let _ = tokio::fs::create_dir_all(parent).await;
     }
-    let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(data.content) else {
-        return;
-    };
-    let client = state.client_config.lock().unwrap().clone();
-    if let Some(reason) = atom_helper_limits::validate_file_size(bytes.len() as u64, &client, &state.cfg) {
-        socket_emit::send_file_size_warning(state, rel.clone(), reason);
-        return;
-    }
+    let size = bytes.len() as u64;
     if let Err(err) = tokio::fs::write(&abs, bytes).await {
         error!("写入失败: {rel}: {err}");
         return;
     }
     state_atoms::mark_client_written(state, &rel);
-    state_atoms::ui_log(state, "info", format!("客户端上传文件: {rel}"));
+    upload_request::record_file(state, size);
+    state_atoms::ui_log_with(
+        state,
+        "info",
+        format!("客户端上传文件: {rel}"),
+        LogFields::new("client_update", &rel).bytes(size),
+    );
 }
 
 pub async fn handle_create_dir(state: &RuntimeState, data: CreateDir) {
@@ -38,41 +62,58 @@ pub async fn handle_create_dir(state: &RuntimeState, data: CreateDir) {
     if crate::watcher::should_ignore_rel(&rel) {
         return;
     }
-    let abs = state.cfg.dir.join(&rel);
+    let abs = state.dir.join(&rel);
     if let Err(err) = tokio::fs::create_dir_all(&abs).await {
         error!("创建目录失败: {rel}: {err}");
         return;
     }
     state_atoms::mark_client_written(state, &rel);
-    state_atoms::ui_log(state, "info", format!("客户端创建目录: {rel}"));
+    upload_request::record_dir(state);
+    state_atoms::ui_log_with(
+        state,
+        "info",
+        format!("客户端创建目录: {rel}"),
+        LogFields::new("client_create_dir", &rel),
+    );
 }
 
 pub fn handle_chunk_start(state: &RuntimeState, data: ChunkStart) {
     let rel = data.path.replace('\\', "/");
-    let abs = state.cfg.dir.join(&rel);
+    let abs = state.dir.join(&rel);
     let client = state.client_config.lock().unwrap().clone();
-    let reject_reason = data
-        .totalSize
-        .and_then(|sz| atom_helper_limits::validate_file_size(sz, &client, &state.cfg));
+    let encoding = WireEncoding::parse(data.encoding.as_deref());
+    let write_path = match encoding {
+        Ok(WireEncoding { compression: Some(_), .. }) => upload_tmp_path(&abs, "packed"),
+        _ => abs.clone(),
+    };
+    let reject_reason = encoding.clone().err().or_else(|| {
+        data.totalSize
+            .and_then(|sz| atom_helper_limits::validate_file_size(&rel, sz, &client, &state_atoms::server_config(state)))
+    });
+    let mut fields = LogFields::new("client_chunk_start", &rel);
+    fields.bytes = data.totalSize;
     state.chunk_receive_state.lock().unwrap().insert(
         data.fileId.clone(),
         ChunkReceiveState {
             abs_path: abs,
+            write_path,
             rel_path: rel,
             received_chunks: 0,
             total_chunks: data.totalChunks,
             reject_reason,
+            encoding: encoding.unwrap_or(WireEncoding::BASE64),
         },
     );
-    state_atoms::ui_log(
+    state_atoms::ui_log_with(
         state,
         "info",
         format!("开始接收分片: {}, 总分片数: {}", data.path, data.totalChunks),
+        fields,
     );
 }
 
-pub async fn handle_chunk_data(state: &RuntimeState, data: ChunkData) {
-    let (abs_path, rel_path) = {
+pub async fn handle_chunk_data(state: &RuntimeState, data: ChunkData, attachment: Option<&[u8]>) {
+    let (write_path, rel_path, encoding) = {
         let map = state.chunk_receive_state.lock().unwrap();
         let Some(st) = map.get(&data.fileId) else {
             let ack = ChunkAck {
@@ -84,7 +125,7 @@ pub async fn handle_chunk_data(state: &RuntimeState, data: ChunkData) {
             socket_emit::emit_chunk_ack(state, &ack);
             return;
         };
-        (st.abs_path.clone(), st.rel_path.clone())
+        (st.write_path.clone(), st.rel_path.clone(), st.encoding)
     };
 
     {
@@ -106,29 +147,33 @@ pub async fn handle_chunk_data(state: &RuntimeState, data: ChunkData) {
         }
     }
 
-    let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(&data.content) else {
-        let ack = ChunkAck {
-            fileId: data.fileId,
-            chunkIndex: data.chunkIndex,
-            success: Some(false),
-            error: Some("Base64 解码失败".to_string()),
-        };
-        socket_emit::emit_chunk_ack(state, &ack);
-        return;
+    // 压缩的分片先按传输编码写入临时文件，`chunk_complete` 时整体解压
+    let bytes = match encoding.transport.decode(&data.content, attachment) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            let ack = ChunkAck {
+                fileId: data.fileId,
+                chunkIndex: data.chunkIndex,
+                success: Some(false),
+                error: Some(err),
+            };
+            socket_emit::emit_chunk_ack(state, &ack);
+            return;
+        }
     };
 
-    if let Some(parent) = abs_path.parent() {
+    if let Some(parent) = write_path.parent() {
         let _ = tokio::fs::create_dir_all(parent).await;
     }
 
     let write_res = if data.chunkIndex == 0 {
-        tokio::fs::write(&abs_path, &bytes).await
+        tokio::fs::write(&write_path, &bytes).await
     } else {
         use tokio::io::AsyncWriteExt;
         let f = tokio::fs::OpenOptions::new()
             .create(true)
             .append(true)
-            .open(&abs_path)
+            .open(&write_path)
             .await;
         match f {
             Ok(mut f) => f.write_all(&bytes).await,
@@ -173,16 +218,60 @@ pub async fn handle_chunk_data(state: &RuntimeState, data: ChunkData) {
     socket_emit::emit_chunk_ack(state, &ack);
 }
 
-pub fn handle_chunk_complete(state: &RuntimeState, data: ChunkComplete) {
-    if let Some(st) = state.chunk_receive_state.lock().unwrap().remove(&data.fileId) {
+/// 压缩上传使用的临时文件：与目标同目录（保证可以原子重命名），后缀被监控忽略。
+fn upload_tmp_path(abs: &Path, stage: &str) -> PathBuf {
+    let mut name = abs.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{stage}{}", crate::watcher::UPLOAD_TMP_SUFFIX));
+    abs.with_file_name(name)
+}
+
+/// 压缩上传的文件在全部分片写入后流式解压到临时文件，输出不超过生效的大小限制；
+/// 成功后才重命名为目标文件，失败时原文件保持不变。
+async fn decompress_uploaded(state: &RuntimeState, st: &ChunkReceiveState, compression: Compression) -> Result<(), String> {
+    let client = state.client_config.lock().unwrap().clone();
+    let limit = atom_helper_limits::decompress_limit(&st.rel_path, &client, &state_atoms::server_config(state));
+    let packed = st.write_path.clone();
+    let unpacked = upload_tmp_path(&st.abs_path, "unpacked");
+    let target = st.abs_path.clone();
+    tokio::task::spawn_blocking(move || {
+        let res = (|| {
+            let input = std::fs::File::open(&packed).map_err(|err| err.to_string())?;
+            let mut output = std::fs::File::create(&unpacked).map_err(|err| err.to_string())?;
+            compression.decompress_to(std::io::BufReader::new(input), &mut output, limit)?;
+            std::fs::rename(&unpacked, &target).map_err(|err| err.to_string())
+        })();
+        let _ = std::fs::remove_file(&packed);
+        if res.is_err() {
+            let _ = std::fs::remove_file(&unpacked);
+        }
+        res
+    })
+    .await
+    .map_err(|err| err.to_string())?
+}
+
+pub async fn handle_chunk_complete(state: &RuntimeState, data: ChunkComplete) {
+    let st = state.chunk_receive_state.lock().unwrap().remove(&data.fileId);
+    if let Some(st) = st {
+        if let (None, Some(compression)) = (&st.reject_reason, st.encoding.compression) {
+            if let Err(reason) = decompress_uploaded(state, &st, compression).await {
+                socket_emit::send_server_warning(state, st.rel_path.clone(), reason);
+                return;
+            }
+        }
         state_atoms::mark_client_written(state, &st.rel_path);
-        state_atoms::ui_log(
+        if st.reject_reason.is_none() {
+            let size = std::fs::metadata(&st.abs_path).map(|m| m.len()).unwrap_or(0);
+            upload_request::record_file(state, size);
+        }
+        state_atoms::ui_log_with(
             state,
             "info",
             format!(
                 "客户端上传分片完成: {:?}, {} 个分片",
                 data.path, st.received_chunks
             ),
+            LogFields::new("client_chunk_complete", &st.rel_path),
         );
         return;
     }
@@ -197,6 +286,8 @@ pub fn handle_chunk_ack(state: &RuntimeState, ack: ChunkAck) {
 }
 
 pub fn disconnect_cleanup(state: &RuntimeState) {
+    upload_request::on_disconnect(state);
+    session_resume::suspend(state);
     state_atoms::clear_socket(state);
     state_atoms::reset_connection_state(state);
 }
@@ -0,0 +1,133 @@
+use crate::config::KnownDevice;
+use crate::proto::ClientHello;
+use crate::server::atoms::{atom_helper_protocol, state as state_atoms};
+use crate::server::molecules::{client_takeover, device_rules, socket_handlers};
+use crate::server::{RuntimeState, UiEvent};
+use crate::settings;
+use serde_json::Value;
+use socketioxide::extract::{Data, SocketRef, State};
+use std::time::Duration;
+
+/// 未知设备等待确认的时长，超时按拒绝处理
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 开启设备确认时的新连接：先只注册 `hello`，凭其中的设备身份自动接受、等待确认或拒绝。
+/// 接受后 emit `device_approved`，客户端据此重新发送 `configure`/`hello`。
+pub fn gate(socket: SocketRef) {
+    // 异步 handler 在事件分发之外执行，接受时才能在同一 socket 上注册其它事件
+    socket.on("hello", |socket: SocketRef, Data(v): Data<Value>, State(state): State<RuntimeState>| async move {
+        let v = state_atoms::extract_first_arg(v);
+        let hello = serde_json::from_value::<ClientHello>(v).unwrap_or_default();
+        if let Err(rejection) = atom_helper_protocol::negotiate(&hello) {
+            socket_handlers::reject_incompatible(state, socket, &hello, rejection);
+            return;
+        }
+        let Some(device) = hello.device() else {
+            let label = client_takeover::device_label(&socket);
+            state_atoms::ui_log(state, "warn", format!("拒绝未声明设备身份的客户端 {label}"));
+            client_takeover::reject(socket, "连接失败：电脑端只接受已确认的设备，请升级客户端后重试");
+            return;
+        };
+        if is_trusted(state, &device) {
+            state_atoms::ui_log(state, "info", format!("已信任设备 {device}，自动接受"));
+            admit(state, socket);
+            return;
+        }
+        ask(state, socket, device);
+    });
+
+    socket.on_disconnect(|socket: SocketRef, State(state): State<RuntimeState>| {
+        if take_pending_if(state, &socket).is_some() {
+            state_atoms::ui_log(state, "info", "等待确认的设备已断开");
+            let _ = state.ui_tx.send(UiEvent::DeviceApprovalResolved);
+        }
+    });
+}
+
+fn is_trusted(state: &RuntimeState, device: &KnownDevice) -> bool {
+    state.cfg.lock().unwrap().trusted_devices.iter().any(|d| d.id == device.id)
+}
+
+fn admit(state: &RuntimeState, socket: SocketRef) {
+    socket_handlers::admit(state, socket.clone());
+    // 进入“询问替换”时等 `takeover_accepted`，这里不重复通知
+    if state_atoms::is_current_socket(state, &socket) {
+        let payload = serde_json::json!({ "action": "device_approved" });
+        socket.emit("device_approved", &payload).ok();
+    }
+}
+
+fn ask(state: &RuntimeState, socket: SocketRef, device: KnownDevice) {
+    {
+        let mut pending = state.pending_device.lock().unwrap();
+        if pending.is_some() {
+            drop(pending);
+            client_takeover::reject(socket, "连接失败：已有其他设备在等待电脑端确认");
+            return;
+        }
+        *pending = Some((socket.clone(), device.clone()));
+    }
+
+    let payload = serde_json::json!({
+        "action": "device_pending",
+        "message": "电脑端尚未信任此设备，等待确认…",
+    });
+    socket.emit("device_pending", &payload).ok();
+    state_atoms::ui_log(
+        state,
+        "warn",
+        format!("未知设备 {device} 请求连接，等待确认（GUI 中允许/信任/拒绝，或输入 approve/trust/deny）"),
+    );
+    let _ = state.ui_tx.send(UiEvent::DeviceApprovalRequested(device.to_string()));
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(APPROVAL_TIMEOUT).await;
+        if let Some(socket) = take_pending_if(&state, &socket) {
+            state_atoms::ui_log(&state, "warn", "设备等待确认超时，已拒绝");
+            let _ = state.ui_tx.send(UiEvent::DeviceApprovalResolved);
+            client_takeover::reject(socket, "连接失败：电脑端未确认此设备");
+        }
+    });
+}
+
+/// 处理等待确认的设备：`remember` 时写入信任列表，之后同一设备自动接受。
+pub fn resolve(state: &RuntimeState, accept: bool, remember: bool) -> Result<(), &'static str> {
+    let (socket, device) = state.pending_device.lock().unwrap().take().ok_or("没有等待确认的设备")?;
+    let _ = state.ui_tx.send(UiEvent::DeviceApprovalResolved);
+    if !accept {
+        state_atoms::ui_log(state, "info", format!("已拒绝设备 {device}"));
+        client_takeover::reject(socket, "连接失败：电脑端拒绝了此设备");
+        return Ok(());
+    }
+    if !socket.connected() {
+        return Err("等待确认的设备已断开");
+    }
+    if remember {
+        trust(state, &device);
+    }
+    *state.approved_device.lock().unwrap() = Some((socket.id.to_string(), device.id.clone()));
+    state_atoms::ui_log(state, "info", format!("已允许设备 {device}"));
+    admit(state, socket);
+    Ok(())
+}
+
+fn trust(state: &RuntimeState, device: &KnownDevice) {
+    {
+        let mut cfg = state.cfg.lock().unwrap();
+        cfg.trusted_devices.retain(|d| d.id != device.id);
+        cfg.trusted_devices.push(device.clone());
+    }
+    let stored = device.clone();
+    device_rules::persist(state, "保存信任设备失败", move || settings::trust_device(&stored));
+    state_atoms::ui_log(state, "info", format!("已将设备 {device} 加入信任列表"));
+    let _ = state.ui_tx.send(UiEvent::DeviceTrusted(device.clone()));
+}
+
+fn take_pending_if(state: &RuntimeState, socket: &SocketRef) -> Option<SocketRef> {
+    let mut pending = state.pending_device.lock().unwrap();
+    if pending.as_ref().is_some_and(|(s, _)| s.id == socket.id) {
+        return pending.take().map(|(s, _)| s);
+    }
+    None
+}
@@ -0,0 +1,66 @@
+use crate::config::{DeviceRules, KnownDevice};
+use crate::proto::ClientConfig;
+use crate::server::atoms::state as state_atoms;
+use crate::server::{RuntimeState, UiEvent};
+use crate::settings;
+
+/// 客户端在 `hello` 中声明设备身份后：以该设备记住的规则为底，叠加本次连接已收到的 `configure`，
+/// 然后把合并结果记为该设备的最新规则。
+pub fn on_identified(state: &RuntimeState, device: &KnownDevice) {
+    *state.device.lock().unwrap() = Some(device.clone());
+    let stored = state.cfg.lock().unwrap().device_rules.get(&device.id).cloned();
+    if let Some(rules) = stored {
+        let merged = {
+            let mut guard = state.client_config.lock().unwrap();
+            let mut base = ClientConfig::default();
+            rules.apply_to(&mut base);
+            let current = std::mem::replace(&mut *guard, base);
+            state_atoms::merge_client_config(&mut guard, current);
+            guard.clone()
+        };
+        state_atoms::rebuild_effective_regex(state, &merged);
+        let _ = state.ui_tx.send(UiEvent::ClientConfigured(merged));
+        state_atoms::ui_log(state, "info", format!("已应用设备 {device} 上次的客户端规则"));
+    }
+    remember(state);
+}
+
+/// 当前设备的规则有变化时写入 `ServerConfig.device_rules` 与配置文件。
+/// 只记录已信任或本次连接经电脑端允许的设备，避免任意客户端凭伪造的设备 id 写入配置文件。
+pub fn remember(state: &RuntimeState) {
+    let Some(device) = state.device.lock().unwrap().clone() else { return };
+    if !is_vouched(state, &device) {
+        return;
+    }
+    let rules = DeviceRules::from_client(&device.name, &state.client_config.lock().unwrap());
+    {
+        let mut cfg = state.cfg.lock().unwrap();
+        if cfg.device_rules.get(&device.id) == Some(&rules) {
+            return;
+        }
+        cfg.device_rules.insert(device.id.clone(), rules.clone());
+    }
+    let (id, stored) = (device.id.clone(), rules.clone());
+    persist(state, "保存设备规则失败", move || settings::store_device_rules(&id, &stored));
+    let _ = state.ui_tx.send(UiEvent::DeviceRulesStored { id: device.id, rules });
+}
+
+fn is_vouched(state: &RuntimeState, device: &KnownDevice) -> bool {
+    if state.cfg.lock().unwrap().trusted_devices.iter().any(|d| d.id == device.id) {
+        return true;
+    }
+    let approved = state.approved_device.lock().unwrap().clone();
+    let socket_id = state.socket.lock().unwrap().as_ref().map(|s| s.id.to_string());
+    approved.is_some_and(|(sid, id)| id == device.id && socket_id.as_deref() == Some(sid.as_str()))
+}
+
+/// 在阻塞线程池中写配置文件（不阻塞事件处理），失败时记录警告。
+pub(crate) fn persist(state: &RuntimeState, failure: &'static str, write: impl FnOnce() -> anyhow::Result<()> + Send + 'static) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        let res = tokio::task::spawn_blocking(write).await.map_err(anyhow::Error::from).and_then(|res| res);
+        if let Err(err) = res {
+            state_atoms::ui_log(&state, "warn", format!("{failure}: {err:#}"));
+        }
+    });
+}
@@ -1,5 +1,5 @@
 use crate::server::atoms::{socket_emit, state as state_atoms};
-use crate::server::molecules::shared_broadcast_entry;
+use crate::server::molecules::{session_resume, shared_broadcast_entry, tree_quota};
 use crate::server::RuntimeState;
 use crate::watcher::{normalize_rel_path, should_ignore_rel, FsEvent, FsEventKind};
 use anyhow::Result;
@@ -7,11 +7,7 @@ use tracing::warn;
 use walkdir::WalkDir;
 
 pub async fn handle_fs_event(state: &RuntimeState, ev: FsEvent) -> Result<()> {
-    if state.socket.lock().unwrap().is_none() {
-        return Ok(());
-    }
-
-    let Some(rel) = normalize_rel_path(&state.cfg.dir, &ev.abs_path) else { return Ok(()) };
+    let Some(rel) = normalize_rel_path(&state.dir, &ev.abs_path) else { return Ok(()) };
     let rel = rel.replace('\\', "/");
     if should_ignore_rel(&rel) {
         return Ok(());
@@ -22,6 +18,11 @@ pub async fn handle_fs_event(state: &RuntimeState, ev: FsEvent) -> Result<()> {
     if state.client_written.lock().unwrap().contains_key(&rel) {
         return Ok(());
     }
+    if state.socket.lock().unwrap().is_none() {
+        // 断线宽限期内记录变更，重连恢复会话后重放
+        session_resume::queue_change(state, &ev);
+        return Ok(());
+    }
 
     match ev.kind {
         FsEventKind::AddFile | FsEventKind::ChangeFile => {
@@ -35,13 +36,23 @@ pub async fn handle_fs_event(state: &RuntimeState, ev: FsEvent) -> Result<()> {
             shared_broadcast_entry::broadcast_file(state, &rel, &ev.abs_path).await?;
         }
         FsEventKind::AddDir => {
+            if tree_quota::enabled(state) {
+                let preview = tree_quota::measure(state, std::slice::from_ref(&rel)).await?;
+                if let Err(reason) = tree_quota::check(state, &preview) {
+                    let message = format!("新目录 {rel}：{reason}");
+                    state_atoms::ui_log(state, "warn", message.clone());
+                    socket_emit::emit_sync_error(state, message);
+                    return Ok(());
+                }
+            }
             shared_broadcast_entry::broadcast_create_dir(state, &rel).await;
 
             // TS 行为：目录创建后，同时广播其当前内容
             let base = ev.abs_path.clone();
+            let generation = state_atoms::connection_generation(state);
             let mut it = WalkDir::new(&base).into_iter();
             while let Some(entry) = it.next().transpose().ok().flatten() {
-                if state.socket.lock().unwrap().is_none() {
+                if !state_atoms::is_same_connection(state, generation) {
                     state_atoms::ui_log(state, "info", "客户端已断开，停止目录遍历");
                     return Ok(());
                 }
@@ -50,7 +61,7 @@ pub async fn handle_fs_event(state: &RuntimeState, ev: FsEvent) -> Result<()> {
                 if abs == base {
                     continue;
                 }
-                let Some(child_rel) = normalize_rel_path(&state.cfg.dir, &abs) else { continue };
+                let Some(child_rel) = normalize_rel_path(&state.dir, &abs) else { continue };
                 let child_rel = child_rel.replace('\\', "/");
                 if should_ignore_rel(&child_rel) {
                     continue;
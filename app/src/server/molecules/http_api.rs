@@ -0,0 +1,70 @@
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+
+use super::path_check::{PathChecker, SyncPreview};
+use super::{sync_all, upload_request};
+use crate::proto::SyncPathRequest;
+use crate::server::atoms::atom_helper_messages;
+use crate::server::RuntimeState;
+use crate::settings;
+
+pub(crate) fn routes(state: RuntimeState) -> Router {
+    Router::new()
+        .route("/api/sync/preview", get(sync_preview))
+        .route("/api/sync/push", post(sync_push))
+        .route("/api/upload/request", post(request_client_upload))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+/// 控制接口要求 `Authorization: Bearer <口令>`（口令文件见 `settings::control_token_path`）。
+async fn require_token(State(state): State<RuntimeState>, req: Request, next: Next) -> Response {
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|token| token.trim() == &*state.control_token);
+    if !authorized {
+        let hint = format!("缺少或错误的控制口令（见 {}）", settings::control_token_path().display());
+        return (StatusCode::UNAUTHORIZED, hint).into_response();
+    }
+    next.run(req).await
+}
+
+/// 以实时过滤规则/大小限制预演 `sync_all`，不向客户端发送任何内容。
+async fn sync_preview(State(state): State<RuntimeState>) -> Result<Json<SyncPreview>, (StatusCode, String)> {
+    let checker = PathChecker::from_state(&state);
+    let dir = state.dir.clone();
+    tokio::task::spawn_blocking(move || checker.preview(&dir))
+        .await
+        .map(Json)
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, format!("预演失败: {err}")))
+}
+
+/// 服务端主动推送；请求体 `{"paths": [...]}` 可选，省略时推送全部文件。
+/// 路径非法返回 400，未连接或已有推送进行中时返回 409。
+async fn sync_push(State(state): State<RuntimeState>, body: Option<Json<SyncPathRequest>>) -> (StatusCode, String) {
+    let paths = body.map(|Json(req)| req.into_paths()).unwrap_or_default();
+    let scope = match sync_all::normalize_scope(&state.dir, &paths) {
+        Ok(scope) => scope,
+        Err(reason) => return (StatusCode::BAD_REQUEST, reason),
+    };
+    let label = atom_helper_messages::format_sync_scope(&scope);
+    match sync_all::start(&state, &scope, &format!("收到 HTTP 推送请求：{label}")) {
+        Ok(()) => (StatusCode::ACCEPTED, format!("已开始推送：{label}")),
+        Err(reason) => (StatusCode::CONFLICT, reason),
+    }
+}
+
+/// 请求客户端上传全部文件（先为同步目录拍快照）；未连接、正在上传或已在请求中时返回 409。
+async fn request_client_upload(State(state): State<RuntimeState>) -> (StatusCode, String) {
+    match upload_request::request(&state) {
+        Ok(()) => (StatusCode::ACCEPTED, "已请求客户端上传全部文件".to_string()),
+        Err(reason) => (StatusCode::CONFLICT, reason.to_string()),
+    }
+}
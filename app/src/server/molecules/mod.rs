@@ -1,5 +1,14 @@
+pub mod client_takeover;
 pub mod client_upload;
+pub mod device_approval;
+pub mod device_rules;
 pub mod fs_broadcast;
+pub mod http_api;
+pub mod path_check;
+pub mod remote_browse;
+pub mod session_resume;
 pub mod shared_broadcast_entry;
 pub mod socket_handlers;
 pub mod sync_all;
+pub mod tree_quota;
+pub mod upload_request;
@@ -0,0 +1,285 @@
+use crate::config::ServerConfig;
+use crate::proto::ClientConfig;
+use crate::server::atoms::{atom_helper_encoding, atom_helper_filter, atom_helper_limits, atom_helper_path, state as state_atoms};
+use crate::server::RuntimeState;
+use regex::Regex;
+use serde::Serialize;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// 单个路径的判定结果，与实际同步时的判断顺序一致：
+/// 内置忽略 -> 服务端规则 -> 客户端规则 -> 文件大小限制。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum Verdict {
+    Sync,
+    BuiltinIgnored,
+    ServerRule(String),
+    ClientRule(String),
+    TooLarge(String),
+}
+
+impl Verdict {
+    pub fn is_skipped(&self) -> bool {
+        !matches!(self, Verdict::Sync)
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Verdict::Sync => "同步".to_string(),
+            Verdict::BuiltinIgnored => "跳过：内置忽略（.DS_Store）".to_string(),
+            Verdict::ServerRule(p) => format!("跳过：命中服务端规则 `{p}`"),
+            Verdict::ClientRule(p) => format!("跳过：命中客户端规则 `{p}`"),
+            Verdict::TooLarge(reason) => format!("跳过：{reason}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PathReport {
+    pub rel: String,
+    pub is_dir: bool,
+    pub verdict: Verdict,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewEntry {
+    pub rel: String,
+    pub is_dir: bool,
+    pub bytes: u64,
+    /// 0 表示整包发送
+    pub chunks: u32,
+}
+
+/// `sync_all` 预演结果：会发送的条目与每个跳过原因，不发送任何内容。
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncPreview {
+    pub files: u64,
+    pub dirs: u64,
+    pub total_bytes: u64,
+    pub chunked_files: u64,
+    pub total_chunks: u64,
+    pub entries: Vec<PreviewEntry>,
+    pub skipped: Vec<PathReport>,
+}
+
+impl SyncPreview {
+    pub fn summary(&self) -> String {
+        format!(
+            "将发送 {} 个文件、{} 个目录，共 {:.1}KB；其中 {} 个文件分片发送（共 {} 个分片）；跳过 {} 项",
+            self.files,
+            self.dirs,
+            self.total_bytes as f64 / 1024.0,
+            self.chunked_files,
+            self.total_chunks,
+            self.skipped.len()
+        )
+    }
+}
+
+/// 只计数的统计结果（配额检查与进度显示使用），不读取文件内容、不记录条目。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TreeTotals {
+    pub files: u64,
+    pub bytes: u64,
+    /// 因大小限制跳过的文件（发送时仍会逐个提示，计入进度）
+    pub too_large: u64,
+    /// 统计中途因 `stop` 条件提前结束，数值只是下限
+    pub truncated: bool,
+}
+
+/// 解析符号链接后的真实路径，必须仍位于同步目录内；不存在时返回错误。含 IO。
+pub fn resolve_within(base: &Path, rel: &str) -> Result<PathBuf, String> {
+    let real_base = std::fs::canonicalize(base).map_err(|err| format!("同步目录不可用: {err}"))?;
+    let real = std::fs::canonicalize(base.join(rel)).map_err(|_| format!("路径不存在: {rel}"))?;
+    if !real.starts_with(&real_base) {
+        return Err(format!("路径指向同步目录之外: {rel}"));
+    }
+    Ok(real)
+}
+
+pub struct PathChecker {
+    cfg: ServerConfig,
+    client: ClientConfig,
+    /// `effective` 中前 `server_rules` 条是服务端规则
+    server_rules: usize,
+    effective: Vec<Regex>,
+}
+
+impl PathChecker {
+    /// 规则经 `atom_helper_filter::effective_regexes` 合并，与实际同步一致。
+    pub fn new(cfg: ServerConfig, client: ClientConfig) -> Self {
+        let server = atom_helper_filter::compile_regexes(&cfg.server_side_path_regex);
+        let effective = atom_helper_filter::effective_regexes(&server, client.pathRegex.as_deref());
+        Self {
+            cfg,
+            client,
+            server_rules: server.len(),
+            effective,
+        }
+    }
+
+    /// 使用运行中服务的实时配置、当前客户端配置（含协商的编码/压缩）与正在生效的规则。
+    pub fn from_state(state: &RuntimeState) -> Self {
+        let client = state_atoms::codec_client_config(state);
+        Self {
+            cfg: state_atoms::server_config(state),
+            client,
+            server_rules: state.server_side_regex.lock().unwrap().len(),
+            effective: state.effective_regex.lock().unwrap().clone(),
+        }
+    }
+
+    pub fn check(&self, rel: &str, is_dir: bool, size: Option<u64>) -> Verdict {
+        let rel = rel.replace('\\', "/");
+        let rel = rel.trim_start_matches("./").trim_start_matches('/');
+        if crate::watcher::should_ignore_rel(rel) {
+            return Verdict::BuiltinIgnored;
+        }
+        if let Some(i) = atom_helper_filter::first_match(rel, &self.effective) {
+            let pattern = self.effective[i].as_str().to_string();
+            return if i < self.server_rules {
+                Verdict::ServerRule(pattern)
+            } else {
+                Verdict::ClientRule(pattern)
+            };
+        }
+        if !is_dir {
+            if let Some(reason) = size.and_then(|s| atom_helper_limits::validate_file_size(rel, s, &self.client, &self.cfg)) {
+                return Verdict::TooLarge(reason);
+            }
+        }
+        Verdict::Sync
+    }
+
+    /// 第一个命中过滤规则的上级目录（与 `sync_all` 相同：被过滤目录下的内容一律不发送）。
+    pub fn filtered_ancestor<'a>(&self, rel: &'a str) -> Option<(&'a str, Verdict)> {
+        atom_helper_path::ancestors(rel).into_iter().find_map(|dir| {
+            let verdict = self.check(dir, true, None);
+            matches!(verdict, Verdict::ServerRule(_) | Verdict::ClientRule(_)).then_some((dir, verdict))
+        })
+    }
+
+    /// 检查同步目录下的相对路径；路径不存在时按文件处理且不检查大小。
+    pub fn check_rel(&self, rel: &str) -> PathReport {
+        let meta = std::fs::metadata(self.cfg.dir.join(rel)).ok();
+        let is_dir = meta.as_ref().is_some_and(|m| m.is_dir());
+        PathReport {
+            rel: rel.to_string(),
+            is_dir,
+            verdict: self.check(rel, is_dir, meta.map(|m| m.len())),
+        }
+    }
+
+    /// 只返回会被跳过的条目。
+    #[cfg_attr(feature = "gui", allow(dead_code))]
+    pub fn dry_run(&self, dir: &Path) -> Vec<PathReport> {
+        self.preview(dir).skipped
+    }
+
+    /// 预演整棵目录树（与 `sync_all` 相同：被过滤的目录不再深入）。
+    pub fn preview(&self, dir: &Path) -> SyncPreview {
+        self.preview_scoped(dir, &[])
+    }
+
+    /// 只预演 `scope` 中的子路径（相对 `dir`，已由 `sync_all::normalize_scope` 规范化）；为空时等同 `preview`。
+    pub fn preview_scoped(&self, dir: &Path, scope: &[String]) -> SyncPreview {
+        let mut preview = SyncPreview::default();
+        if scope.is_empty() {
+            self.preview_root(dir, dir, &mut preview);
+        }
+        for rel in scope {
+            self.preview_root(dir, &dir.join(rel), &mut preview);
+        }
+        preview
+    }
+
+    /// 按当前协商的编码实际编码一次，得到与发送时一致的分片数；小文件不读取内容。
+    fn chunk_count(&self, abs: &Path, bytes: u64) -> u32 {
+        if atom_helper_limits::fits_single_chunk(bytes) {
+            return 0;
+        }
+        std::fs::read(abs)
+            .map(|data| atom_helper_limits::chunk_count(&atom_helper_encoding::encode_for_client(data, &self.client)))
+            .unwrap_or(0)
+    }
+
+    /// 只统计 `scope` 中会发送的文件数与字节数（为空表示整个 `dir`）；`stop(files, bytes)` 返回 true 时立即结束。
+    pub fn count_scoped(&self, dir: &Path, scope: &[String], stop: impl Fn(u64, u64) -> bool) -> TreeTotals {
+        let mut totals = TreeTotals::default();
+        let roots: Vec<PathBuf> = if scope.is_empty() {
+            vec![dir.to_path_buf()]
+        } else {
+            scope.iter().map(|rel| dir.join(rel)).collect()
+        };
+        for root in roots {
+            let flow = self.walk(dir, &root, |_, _, is_dir, size, verdict| {
+                match verdict {
+                    Verdict::Sync if !is_dir => {
+                        totals.files += 1;
+                        totals.bytes += size.unwrap_or(0);
+                        if stop(totals.files, totals.bytes) {
+                            return ControlFlow::Break(());
+                        }
+                    }
+                    Verdict::TooLarge(_) => totals.too_large += 1,
+                    _ => {}
+                }
+                ControlFlow::Continue(())
+            });
+            if flow.is_break() {
+                totals.truncated = true;
+                break;
+            }
+        }
+        totals
+    }
+
+    /// 与 `sync_all` 相同的遍历：被过滤的目录不再深入（内置忽略的除外），每个条目连同判定结果交给 `visit`。
+    fn walk(
+        &self,
+        dir: &Path,
+        root: &Path,
+        mut visit: impl FnMut(&Path, String, bool, Option<u64>, Verdict) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let mut it = WalkDir::new(root).into_iter();
+        while let Some(entry) = it.next().transpose().ok().flatten() {
+            if entry.path() == dir {
+                continue;
+            }
+            let Some(rel) = crate::watcher::normalize_rel_path(dir, entry.path()) else { continue };
+            let is_dir = entry.file_type().is_dir();
+            let size = (!is_dir).then(|| entry.metadata().ok().map(|m| m.len())).flatten();
+            let verdict = self.check(&rel, is_dir, size);
+            if is_dir && verdict.is_skipped() && verdict != Verdict::BuiltinIgnored {
+                it.skip_current_dir();
+            }
+            visit(entry.path(), rel, is_dir, size, verdict)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn preview_root(&self, dir: &Path, root: &Path, preview: &mut SyncPreview) {
+        let _ = self.walk(dir, root, |abs, rel, is_dir, size, verdict| {
+            if !verdict.is_skipped() {
+                let bytes = size.unwrap_or(0);
+                let chunks = if is_dir { 0 } else { self.chunk_count(abs, bytes) };
+                if is_dir {
+                    preview.dirs += 1;
+                } else {
+                    preview.files += 1;
+                    preview.total_bytes += bytes;
+                    if chunks > 0 {
+                        preview.chunked_files += 1;
+                        preview.total_chunks += u64::from(chunks);
+                    }
+                }
+                preview.entries.push(PreviewEntry { rel, is_dir, bytes, chunks });
+            } else {
+                preview.skipped.push(PathReport { rel, is_dir, verdict });
+            }
+            ControlFlow::Continue(())
+        });
+    }
+}
@@ -0,0 +1,112 @@
+use crate::proto::{DirListing, RemoteEntry};
+use crate::server::atoms::{atom_helper_path, socket_emit, state as state_atoms};
+use crate::server::molecules::path_check::{self, PathChecker};
+use crate::server::molecules::shared_broadcast_entry;
+use crate::server::RuntimeState;
+use std::time::UNIX_EPOCH;
+
+/// 列出同步目录下的一层子条目（目录在前、按名称排序），每项标记是否会被过滤。
+pub async fn list_dir(state: &RuntimeState, raw: &str) -> Result<DirListing, String> {
+    let rel = atom_helper_path::to_rel(&state.dir, raw)?;
+    let checker = PathChecker::from_state(state);
+    reject_filtered(&checker, &rel, true)?;
+    let base = state.dir.clone();
+    let path = rel.clone();
+    let entries = tokio::task::spawn_blocking(move || {
+        let abs = path_check::resolve_within(&base, &rel)?;
+        let read = std::fs::read_dir(&abs).map_err(|err| format!("无法读取目录 {rel}: {err}"))?;
+        let mut entries = Vec::new();
+        for entry in read.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = atom_helper_path::join_rel(&rel, &name);
+            // 指向同步目录之外的符号链接不列出
+            if path_check::resolve_within(&base, &path).is_err() {
+                continue;
+            }
+            let Ok(meta) = entry.metadata() else { continue };
+            let verdict = checker.check(&path, meta.is_dir(), Some(meta.len()));
+            let mtime = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64);
+            entries.push(RemoteEntry {
+                name,
+                path,
+                isDir: meta.is_dir(),
+                size: if meta.is_dir() { 0 } else { meta.len() },
+                mtime,
+                filtered: verdict.is_skipped(),
+                reason: verdict.is_skipped().then(|| verdict.describe()),
+            });
+        }
+        entries.sort_by(|a, b| b.isDir.cmp(&a.isDir).then_with(|| a.name.cmp(&b.name)));
+        Ok::<_, String>(entries)
+    })
+    .await
+    .map_err(|err| format!("读取目录失败: {err}"))??;
+
+    Ok(DirListing {
+        action: "dir_listing".to_string(),
+        path,
+        entries,
+        error: None,
+    })
+}
+
+/// 按需发送单个文件，与推送相同走 `update`/分片流程；被过滤或超出大小限制时返回原因。
+pub async fn fetch_file(state: &RuntimeState, raw: &str) -> Result<(), String> {
+    let rel = atom_helper_path::to_rel(&state.dir, raw)?;
+    let checker = PathChecker::from_state(state);
+    reject_filtered(&checker, &rel, false)?;
+    let abs = path_check::resolve_within(&state.dir, &rel).map_err(|_| format!("文件不存在或不在同步目录内: {rel}"))?;
+    let meta = tokio::fs::metadata(&abs)
+        .await
+        .map_err(|_| format!("文件不存在: {rel}"))?;
+    if !meta.is_file() {
+        return Err(format!("不是文件: {rel}"));
+    }
+    let verdict = checker.check(&rel, false, Some(meta.len()));
+    if verdict.is_skipped() {
+        return Err(format!("{rel}: {}", verdict.describe()));
+    }
+
+    state_atoms::ui_log(state, "info", format!("客户端请求文件: {rel}"));
+    shared_broadcast_entry::broadcast_file(state, &rel, &abs)
+        .await
+        .map_err(|err| format!("发送失败: {rel}: {err:#}"))
+}
+
+/// 上级目录（列目录时包括目录自身）命中过滤规则时拒绝，与 `sync_all` 不进入被过滤目录一致。
+fn reject_filtered(checker: &PathChecker, rel: &str, is_dir: bool) -> Result<(), String> {
+    if let Some((dir, verdict)) = checker.filtered_ancestor(rel) {
+        return Err(format!("{rel}: 上级目录 {dir} {}", verdict.describe()));
+    }
+    if is_dir && !rel.is_empty() {
+        let verdict = checker.check(rel, true, None);
+        if verdict.is_skipped() {
+            return Err(format!("{rel}: {}", verdict.describe()));
+        }
+    }
+    Ok(())
+}
+
+pub async fn handle_list_dir(state: &RuntimeState, raw: &str) {
+    let listing = list_dir(state, raw).await.unwrap_or_else(|err| {
+        state_atoms::ui_log(state, "warn", format!("list_dir 失败: {err}"));
+        DirListing {
+            action: "dir_listing".to_string(),
+            path: raw.to_string(),
+            entries: Vec::new(),
+            error: Some(err),
+        }
+    });
+    socket_emit::emit_dir_listing(state, &listing);
+}
+
+pub async fn handle_fetch_file(state: &RuntimeState, raw: &str) {
+    if let Err(err) = fetch_file(state, raw).await {
+        state_atoms::ui_log(state, "warn", format!("fetch_file 失败: {err}"));
+        socket_emit::emit_fetch_error(state, raw, err);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::proto::SuspendedSession;
+use crate::server::atoms::{socket_emit, state as state_atoms, token};
+use crate::server::molecules::fs_broadcast;
+use crate::server::RuntimeState;
+use crate::watcher::FsEvent;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// 离线期间最多记录的变更数，超过后恢复时只提示重新同步
+const MAX_QUEUED: usize = 1000;
+
+/// 握手协商了 `resume` 时为当前连接分配随机会话 id；会话在断开时绑定到当前设备 id。
+/// 无法生成时本次连接不支持恢复。
+pub fn issue(state: &RuntimeState) -> Option<String> {
+    let id = match token::random_token() {
+        Ok(id) => id,
+        Err(err) => {
+            state_atoms::ui_log(state, "warn", format!("{err:#}，本次连接不支持会话恢复"));
+            return None;
+        }
+    };
+    *state.session_id.lock().unwrap() = Some(id.clone());
+    Some(id)
+}
+
+/// 断开时保留会话（未分配会话或宽限期为 0 时不保留）。
+pub fn suspend(state: &RuntimeState) {
+    let grace = state_atoms::server_config(state).resume_grace_secs;
+    let Some(id) = state.session_id.lock().unwrap().clone() else { return };
+    if grace == 0 {
+        return;
+    }
+    let session = SuspendedSession {
+        id: id.clone(),
+        device_id: state.device.lock().unwrap().as_ref().map(|d| d.id.clone()),
+        expires: Instant::now() + Duration::from_secs(grace),
+        client_config: state.client_config.lock().unwrap().clone(),
+        chunk_receive_state: std::mem::take(&mut *state.chunk_receive_state.lock().unwrap()),
+        client_written: state.client_written.lock().unwrap().keys().cloned().collect(),
+        queued: Vec::new(),
+        overflowed: false,
+        sync_interrupted: state.sync_running.load(Ordering::SeqCst),
+    };
+    *state.suspended.lock().unwrap() = Some(session);
+    state_atoms::ui_log(state, "info", format!("保留客户端会话 {grace} 秒，等待重连"));
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(grace)).await;
+        let expired = {
+            let mut guard = state.suspended.lock().unwrap();
+            if guard.as_ref().is_some_and(|s| s.id == id) {
+                guard.take()
+            } else {
+                None
+            }
+        };
+        if let Some(session) = expired {
+            release(session);
+            state_atoms::ui_log(&state, "info", "客户端未在宽限期内重连，会话已清理");
+        }
+    });
+}
+
+/// 会话保留期间记录本地变更；没有保留的会话时直接丢弃。
+pub fn queue_change(state: &RuntimeState, ev: &FsEvent) {
+    let mut guard = state.suspended.lock().unwrap();
+    let Some(session) = guard.as_mut() else { return };
+    session.queued.retain(|q| q.abs_path != ev.abs_path);
+    if session.queued.len() >= MAX_QUEUED {
+        session.overflowed = true;
+        session.queued.clear();
+    }
+    if !session.overflowed {
+        session.queued.push(ev.clone());
+    }
+}
+
+/// 凭 `sessionId` 恢复会话：还原客户端配置（本次 `configure` 已声明的字段优先）、分片接收状态与回声抑制，
+/// 返回待重放的离线变更。没有对应的会话、已过期或设备 id 不一致时返回原因。
+/// 断线时进行中的服务端发送（下载全部、`sync_path`、`fetch_file`）不会续传，只提示客户端重新发起。
+pub fn restore(state: &RuntimeState, id: &str, device_id: Option<&str>) -> Result<Vec<FsEvent>, String> {
+    let session = {
+        let mut guard = state.suspended.lock().unwrap();
+        match guard.take() {
+            Some(s) if s.id == id && s.device_id.as_deref() != device_id => {
+                *guard = Some(s);
+                return Err("会话不属于该设备".to_string());
+            }
+            Some(s) if s.id == id && s.expires > Instant::now() => s,
+            Some(s) if s.id == id => {
+                drop(guard);
+                release(s);
+                return Err("会话已过期".to_string());
+            }
+            other => {
+                *guard = other;
+                return Err("会话不存在".to_string());
+            }
+        }
+    };
+
+    let merged = {
+        let mut guard = state.client_config.lock().unwrap();
+        let current = std::mem::replace(&mut *guard, session.client_config);
+        state_atoms::merge_client_config(&mut guard, current);
+        guard.clone()
+    };
+    state_atoms::rebuild_effective_regex(state, &merged);
+    let _ = state.ui_tx.send(crate::server::UiEvent::ClientConfigured(merged));
+    state.chunk_receive_state.lock().unwrap().extend(session.chunk_receive_state);
+    for rel in &session.client_written {
+        state_atoms::mark_client_written(state, rel);
+    }
+    *state.session_id.lock().unwrap() = Some(session.id);
+
+    if session.sync_interrupted {
+        socket_emit::send_server_warning(state, "会话恢复".to_string(), "断线前的同步未完成，不会自动续传，请重新下载".to_string());
+    }
+
+    if session.overflowed {
+        socket_emit::send_server_warning(
+            state,
+            "会话恢复".to_string(),
+            format!("离线期间本地变更超过 {MAX_QUEUED} 项，未重放，请重新下载全部"),
+        );
+        return Ok(Vec::new());
+    }
+    state_atoms::ui_log(state, "info", format!("会话已恢复，重放离线期间的 {} 项变更", session.queued.len()));
+    Ok(session.queued)
+}
+
+pub async fn replay(state: &RuntimeState, events: Vec<FsEvent>) {
+    let generation = state_atoms::connection_generation(state);
+    for ev in events {
+        if !state_atoms::is_same_connection(state, generation) {
+            state_atoms::ui_log(state, "info", "连接已更换，停止重放离线变更");
+            return;
+        }
+        if let Err(err) = fs_broadcast::handle_fs_event(state, ev).await {
+            warn!("重放变更失败: {err:#}");
+        }
+    }
+}
+
+/// 新连接没有恢复会话时丢弃保留的会话。
+pub fn discard(state: &RuntimeState) {
+    let session = state.suspended.lock().unwrap().take();
+    if let Some(session) = session {
+        release(session);
+        state_atoms::ui_log(state, "info", "新连接未恢复会话，已丢弃保留的会话");
+    }
+}
+
+/// 不再恢复的会话：删除其中未完成的压缩上传临时文件。
+fn release(session: SuspendedSession) {
+    state_atoms::remove_chunk_temp_files(session.chunk_receive_state.into_values());
+}
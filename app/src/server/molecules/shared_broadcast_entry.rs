@@ -1,13 +1,11 @@
 use crate::proto::{ChunkComplete, ChunkData, ChunkStart};
-use crate::server::atoms::{atom_helper_limits, socket_emit, state as state_atoms};
+use crate::server::atoms::state::{self as state_atoms, LogFields};
+use crate::server::atoms::{atom_helper_encoding, atom_helper_limits, socket_emit};
 use crate::server::RuntimeState;
 use anyhow::Result;
-use base64::Engine;
 use std::path::Path;
 use tokio::time::Duration;
 
-const CHUNK_SIZE: usize = 256 * 1024;
-
 pub async fn broadcast_create_dir(state: &RuntimeState, rel: &str) {
     if state_atoms::should_filter_rel(state, rel) {
         socket_emit::send_server_warning(
@@ -42,25 +40,29 @@ pub async fn broadcast_file(state: &RuntimeState, rel: &str, abs: &Path) -> Resu
         return Ok(());
     }
 
-    let client = state.client_config.lock().unwrap().clone();
+    let client = state_atoms::codec_client_config(state);
     let meta = tokio::fs::metadata(abs).await?;
-    if let Some(reason) = atom_helper_limits::validate_file_size(meta.len(), &client, &state.cfg) {
+    if let Some(reason) = atom_helper_limits::validate_file_size(rel, meta.len(), &client, &state_atoms::server_config(state)) {
         socket_emit::send_file_size_warning(state, rel.to_string(), reason);
         return Ok(());
     }
 
     let bytes = tokio::fs::read(abs).await?;
-    let b64 = base64::engine::general_purpose::STANDARD.encode(bytes);
+    let content = atom_helper_encoding::encode_for_client(bytes, &client);
+    let encoding = content.encoding;
 
-    if b64.len() <= CHUNK_SIZE {
-        socket_emit::emit_update_small(state, rel, b64);
+    if content.wire_len() <= atom_helper_limits::CHUNK_SIZE {
+        socket_emit::emit_update_small(state, rel, content, meta.len());
         return Ok(());
     }
 
-    let total_chunks = ((b64.len() as f64) / (CHUNK_SIZE as f64)).ceil() as u32;
+    // 连接被替换或断开后重连时停止发送，避免把旧连接的分片发给新客户端
+    let generation = state_atoms::connection_generation(state);
+    let chunks = content.chunks(atom_helper_limits::CHUNK_SIZE);
+    let total_chunks = chunks.len() as u32;
     for file_retry in 0..=3 {
-        if state.socket.lock().unwrap().is_none() {
-            state_atoms::ui_log(state, "info", format!("客户端已断开，停止发送: {rel}"));
+        if !state_atoms::is_same_connection(state, generation) {
+            state_atoms::ui_log(state, "info", format!("客户端已断开或被替换，停止发送: {rel}"));
             return Ok(());
         }
 
@@ -76,41 +78,40 @@ pub async fn broadcast_file(state: &RuntimeState, rel: &str, abs: &Path) -> Resu
             totalChunks: total_chunks,
             totalSize: Some(meta.len()),
             isDir: Some(false),
+            encoding: Some(encoding.name()),
         };
         socket_emit::emit_chunk_start(state, &start);
-        state_atoms::ui_log(
+        state_atoms::ui_log_with(
             state,
             "info",
             format!("开始发送分片: {rel}, {total_chunks} 个分片"),
+            LogFields::new("chunk_start", rel).bytes(meta.len()),
         );
 
         let mut file_ok = true;
         for chunk_index in 0..total_chunks {
-            if state.socket.lock().unwrap().is_none() {
-                state_atoms::ui_log(state, "info", format!("客户端已断开，停止发送: {rel}"));
+            if !state_atoms::is_same_connection(state, generation) {
+                state_atoms::ui_log(state, "info", format!("客户端已断开或被替换，停止发送: {rel}"));
                 file_ok = false;
                 break;
             }
 
-            let start_i = (chunk_index as usize) * CHUNK_SIZE;
-            let end_i = ((chunk_index as usize + 1) * CHUNK_SIZE).min(b64.len());
-            let chunk_content = b64[start_i..end_i].to_string();
             let payload = ChunkData {
                 fileId: file_id.clone(),
                 chunkIndex: chunk_index,
-                content: chunk_content,
+                content: chunks[chunk_index as usize].0.to_string(),
                 path: Some(rel.to_string()),
             };
 
             let mut ok = false;
             for retry in 0..=3 {
-                if state.socket.lock().unwrap().is_none() {
+                if !state_atoms::is_same_connection(state, generation) {
                     break;
                 }
                 if retry > 0 {
                     tokio::time::sleep(Duration::from_secs(1)).await;
                 }
-                if socket_emit::send_chunk_and_wait_ack(state, &file_id, chunk_index, &payload)
+                if socket_emit::send_chunk_and_wait_ack(state, &file_id, chunk_index, &payload, chunks[chunk_index as usize].1.as_ref())
                     .await?
                 {
                     ok = true;
@@ -144,7 +145,12 @@ pub async fn broadcast_file(state: &RuntimeState, rel: &str, abs: &Path) -> Resu
                 path: Some(rel.to_string()),
             };
             socket_emit::emit_chunk_complete(state, &complete);
-            state_atoms::ui_log(state, "info", format!("分片发送完成: {rel}"));
+            state_atoms::ui_log_with(
+                state,
+                "info",
+                format!("分片发送完成: {rel}"),
+                LogFields::new("chunk_complete", rel).bytes(meta.len()),
+            );
             break;
         }
     }
@@ -1,66 +1,157 @@
 use serde_json::Value;
-use socketioxide::extract::{Data, SocketRef, State};
-use tracing::error;
+use socketioxide::extract::{Bin, Data, SocketRef, State};
 
 use crate::proto::{
-    ChunkAck, ChunkComplete, ChunkData, ChunkStart, ClientConfig, ConnectionRejected, CreateDir, UpdateFile,
+    ChunkAck, ChunkComplete, ChunkData, ChunkStart, ClientConfig, ClientHello, ConnectionRejected, CreateDir, RemotePathRequest, SyncPathRequest,
+    UpdateFile,
 };
 
-use super::{client_upload, sync_all};
-use crate::server::atoms::{socket_emit, state as state_atoms};
+use super::{client_takeover, client_upload, device_approval, device_rules, remote_browse, session_resume, sync_all, upload_request};
+use crate::server::atoms::atom_helper_protocol::{self, Rejection};
+use crate::server::atoms::{atom_helper_filter, atom_helper_messages, socket_emit, state as state_atoms};
 use crate::server::RuntimeState;
 
 pub(crate) fn on_connect(socket: SocketRef, Data(data): Data<Value>, State(state): State<RuntimeState>) {
     let _ = data;
 
-    if !state_atoms::set_socket_if_empty(&state, socket.clone()) {
-        let payload = ConnectionRejected {
-            message: "连接失败：已有其他客户端连接，不允许多个客户端同时连接".to_string(),
-        };
-        socket.emit("connection_rejected", &payload).ok();
-        socket.disconnect().ok();
+    if state_atoms::server_config(state).require_device_approval {
+        device_approval::gate(socket);
         return;
     }
+    admit(state, socket);
+}
 
-    state_atoms::ui_log(&state, "info", "客户端连接");
-    let _ = state.ui_tx.send(crate::server::UiEvent::ClientConnected);
+/// 设为当前客户端；已有客户端时交给 `client_takeover::on_busy`。
+pub(crate) fn admit(state: &RuntimeState, socket: SocketRef) {
+    if !state_atoms::set_socket_if_empty(state, socket.clone()) {
+        client_takeover::on_busy(state, socket);
+        return;
+    }
+    accept(state, socket);
+}
+
+/// 握手协商失败：告知原因与升级提示后断开。
+pub(crate) fn reject_incompatible(state: &RuntimeState, socket: SocketRef, hello: &ClientHello, rejection: Rejection) {
+    state_atoms::ui_log(
+        state,
+        "warn",
+        format!("拒绝客户端（版本 {}）: {}", hello.clientVersion.as_deref().unwrap_or("未知"), rejection.message),
+    );
+    let payload = ConnectionRejected {
+        message: format!("连接失败：{}", rejection.message),
+        upgradeHint: Some(rejection.upgrade_hint),
+    };
+    socket.emit("connection_rejected", &payload).ok();
+    socket.disconnect().ok();
+}
+
+/// 接受已设为当前客户端的 socket：写日志并注册事件。
+pub(crate) fn accept(state: &RuntimeState, socket: SocketRef) {
+    let device = client_takeover::device_label(&socket);
+    state_atoms::ui_log(state, "info", format!("客户端连接: {device}"));
+    let _ = state.ui_tx.send(crate::server::UiEvent::ClientConnected(device));
+
+    socket.on("hello", |socket: SocketRef, Data(v): Data<Value>, State(state): State<RuntimeState>| async move {
+        let v = state_atoms::extract_first_arg(v);
+        let hello = serde_json::from_value::<ClientHello>(v).unwrap_or_default();
+        match atom_helper_protocol::negotiate(&hello) {
+            Ok(peer) => {
+                state_atoms::ui_log(state, "info", atom_helper_messages::format_hello(&peer));
+                if let Some(device) = hello.device() {
+                    let label = device.to_string();
+                    state_atoms::ui_log(state, "info", format!("客户端设备: {label}"));
+                    state_atoms::identify_client(state, &label);
+                    device_rules::on_identified(state, &device);
+                }
+                let restored = match hello.sessionId.as_deref().filter(|_| peer.has("resume")) {
+                    Some(id) => session_resume::restore(state, id, hello.device().as_ref().map(|d| d.id.as_str()))
+                        .inspect_err(|reason| state_atoms::ui_log(state, "warn", format!("无法恢复会话: {reason}")))
+                        .ok(),
+                    None => None,
+                };
+                let session_id = match &restored {
+                    Some(_) => state.session_id.lock().unwrap().clone(),
+                    None => {
+                        session_resume::discard(state);
+                        peer.has("resume").then(|| session_resume::issue(state)).flatten()
+                    }
+                };
+                socket_emit::emit_hello(state, &peer, session_id, restored.is_some());
+                *state.peer.lock().unwrap() = Some(peer);
+                if let Some(events) = restored {
+                    session_resume::replay(state, events).await;
+                }
+            }
+            Err(rejection) => reject_incompatible(state, socket, &hello, rejection),
+        }
+    });
 
     socket.on("configure", |Data(v): Data<Value>, State(state): State<RuntimeState>| {
         let v = state_atoms::extract_first_arg(v);
         if let Ok(cfg) = serde_json::from_value::<ClientConfig>(v) {
+            for (_, pattern, err) in atom_helper_filter::invalid_regexes(cfg.pathRegex.as_deref().unwrap_or_default()) {
+                socket_emit::send_server_warning(state, pattern, format!("客户端过滤规则无效，已忽略（{err}）"));
+            }
             let merged = {
                 let mut guard = state.client_config.lock().unwrap();
                 state_atoms::merge_client_config(&mut guard, cfg.clone());
                 guard.clone()
             };
-            state_atoms::rebuild_effective_regex(&state, &merged);
-            state_atoms::ui_log(&state, "info", format!("更新客户端配置: {:?}", cfg));
+            state_atoms::rebuild_effective_regex(state, &merged);
+            let _ = state.ui_tx.send(crate::server::UiEvent::ClientConfigured(merged));
+            state_atoms::ui_log(state, "info", format!("更新客户端配置: {:?}", cfg));
+            device_rules::remember(state);
+        }
+    });
+
+    socket.on("sync_all", |State(state): State<RuntimeState>| {
+        if let Err(reason) = sync_all::start(state, &[], "收到客户端下载请求：sync_all") {
+            state_atoms::ui_log(state, "warn", format!("忽略 sync_all: {reason}"));
+            socket_emit::emit_sync_error(state, reason);
         }
     });
 
-    socket.on("sync_all", |State(state): State<RuntimeState>| async move {
-        state_atoms::ui_log(&state, "info", "收到客户端下载请求：sync_all");
-        if let Err(err) = sync_all::run(&state).await {
-            error!("sync_all 失败: {err:#}");
-            state_atoms::ui_log(&state, "error", format!("上传全部失败: {err:#}"));
-            socket_emit::emit_sync_error(&state, err.to_string());
+    socket.on("sync_path", |Data(v): Data<Value>, State(state): State<RuntimeState>| {
+        let v = state_atoms::extract_first_arg(v);
+        let paths = serde_json::from_value::<SyncPathRequest>(v).unwrap_or_default().into_paths();
+        if paths.is_empty() {
+            state_atoms::ui_log(state, "warn", "忽略 sync_path: 未提供路径");
+            socket_emit::emit_sync_error(state, "sync_path 未提供路径".to_string());
+            return;
+        }
+        let trigger = format!("收到客户端下载请求：sync_path {}", paths.join(", "));
+        if let Err(reason) = sync_all::start(state, &paths, &trigger) {
+            state_atoms::ui_log(state, "warn", format!("忽略 sync_path: {reason}"));
+            socket_emit::emit_sync_error(state, reason);
         }
     });
 
+    socket.on("list_dir", |Data(v): Data<Value>, State(state): State<RuntimeState>| async move {
+        let v = state_atoms::extract_first_arg(v);
+        let req = serde_json::from_value::<RemotePathRequest>(v).unwrap_or_default();
+        remote_browse::handle_list_dir(state, &req.path).await;
+    });
+
+    socket.on("fetch_file", |Data(v): Data<Value>, State(state): State<RuntimeState>| async move {
+        let v = state_atoms::extract_first_arg(v);
+        let req = serde_json::from_value::<RemotePathRequest>(v).unwrap_or_default();
+        remote_browse::handle_fetch_file(state, &req.path).await;
+    });
+
     socket.on("client_upload_start", |State(state): State<RuntimeState>| {
-        state_atoms::ui_log(&state, "info", "客户端开始上传全部文件...");
+        upload_request::on_upload_start(state);
     });
 
     socket.on("client_upload_complete", |State(state): State<RuntimeState>| {
-        state_atoms::ui_log(&state, "info", "客户端上传全部完成");
+        upload_request::on_upload_complete(state);
     });
 
-    socket.on("update", |Data(v): Data<Value>, State(state): State<RuntimeState>| async move {
+    socket.on("update", |Data(v): Data<Value>, State(state): State<RuntimeState>, Bin(bin): Bin| async move {
         let v = state_atoms::extract_first_arg(v);
         let Ok(data) = serde_json::from_value::<UpdateFile>(v) else {
             return;
         };
-        client_upload::handle_update(&state, data).await;
+        client_upload::handle_update(state, data, bin.first().map(|b| b.as_ref())).await;
     });
 
     socket.on("create_dir", |Data(v): Data<Value>, State(state): State<RuntimeState>| async move {
@@ -68,7 +159,7 @@ pub(crate) fn on_connect(socket: SocketRef, Data(data): Data<Value>, State(state
         let Ok(data) = serde_json::from_value::<CreateDir>(v) else {
             return;
         };
-        client_upload::handle_create_dir(&state, data).await;
+        client_upload::handle_create_dir(state, data).await;
     });
 
     socket.on("chunk_start", |Data(v): Data<Value>, State(state): State<RuntimeState>| {
@@ -76,23 +167,23 @@ pub(crate) fn on_connect(socket: SocketRef, Data(data): Data<Value>, State(state
         let Ok(data) = serde_json::from_value::<ChunkStart>(v) else {
             return;
         };
-        client_upload::handle_chunk_start(&state, data);
+        client_upload::handle_chunk_start(state, data);
     });
 
-    socket.on("chunk_data", |Data(v): Data<Value>, State(state): State<RuntimeState>| async move {
+    socket.on("chunk_data", |Data(v): Data<Value>, State(state): State<RuntimeState>, Bin(bin): Bin| async move {
         let v = state_atoms::extract_first_arg(v);
         let Ok(data) = serde_json::from_value::<ChunkData>(v) else {
             return;
         };
-        client_upload::handle_chunk_data(&state, data).await;
+        client_upload::handle_chunk_data(state, data, bin.first().map(|b| b.as_ref())).await;
     });
 
-    socket.on("chunk_complete", |Data(v): Data<Value>, State(state): State<RuntimeState>| {
+    socket.on("chunk_complete", |Data(v): Data<Value>, State(state): State<RuntimeState>| async move {
         let v = state_atoms::extract_first_arg(v);
         let Ok(data) = serde_json::from_value::<ChunkComplete>(v) else {
             return;
         };
-        client_upload::handle_chunk_complete(&state, data);
+        client_upload::handle_chunk_complete(state, data).await;
     });
 
     socket.on("chunk_ack", |Data(v): Data<Value>, State(state): State<RuntimeState>| {
@@ -100,12 +191,16 @@ pub(crate) fn on_connect(socket: SocketRef, Data(data): Data<Value>, State(state
         let Ok(ack) = serde_json::from_value::<ChunkAck>(v) else {
             return;
         };
-        client_upload::handle_chunk_ack(&state, ack);
+        client_upload::handle_chunk_ack(state, ack);
     });
 
-    socket.on_disconnect(|_socket: SocketRef, State(state): State<RuntimeState>| async move {
-        state_atoms::ui_log(&state, "warn", "客户端断开连接");
+    socket.on_disconnect(|socket: SocketRef, State(state): State<RuntimeState>| async move {
+        // 被替换/断开的旧连接已在 `client_takeover::evict` 中清理
+        if !state_atoms::is_current_socket(state, &socket) {
+            return;
+        }
+        state_atoms::ui_log(state, "warn", "客户端断开连接");
         let _ = state.ui_tx.send(crate::server::UiEvent::ClientDisconnected);
-        client_upload::disconnect_cleanup(&state);
+        client_upload::disconnect_cleanup(state);
     });
 }
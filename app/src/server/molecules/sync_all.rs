@@ -1,63 +1,167 @@
-use crate::server::atoms::{socket_emit, state as state_atoms};
-use crate::server::molecules::shared_broadcast_entry;
-use crate::server::RuntimeState;
+use crate::server::atoms::{atom_helper_messages, atom_helper_path, socket_emit, state as state_atoms};
+use crate::server::molecules::path_check::{self, TreeTotals};
+use crate::server::molecules::{shared_broadcast_entry, tree_quota};
+use crate::server::{RuntimeState, UiEvent};
 use anyhow::Result;
+use std::path::{Path, PathBuf};
 use tracing::warn;
 use walkdir::WalkDir;
 
-pub async fn run(state: &RuntimeState) -> Result<()> {
-    if state.socket.lock().unwrap().is_none() {
-        return Ok(());
+/// 规范化 `sync_path` 的路径列表：统一分隔符、去掉首尾 `/`、去重并合并已被父目录包含的路径。
+/// 同步目录内的绝对路径会转换为相对路径；目录外（包括经符号链接指向目录外）、含 `..` 或不存在的路径返回错误。
+/// 返回空列表表示整个同步目录。
+pub fn normalize_scope(base: &Path, paths: &[String]) -> Result<Vec<String>, String> {
+    let mut out = Vec::new();
+    for raw in paths {
+        let rel = atom_helper_path::to_rel(base, raw)?;
+        if rel.is_empty() {
+            return Ok(Vec::new());
+        }
+        path_check::resolve_within(base, &rel)?;
+        out.push(rel);
     }
+    out.sort();
+    out.dedup();
 
-    state_atoms::ui_log(state, "info", "开始上传全部...");
-    socket_emit::emit_sync_control(state, "sync_start");
-
-    let base = state.cfg.dir.clone();
-    let mut it = WalkDir::new(&base).into_iter();
-    while let Some(entry) = it.next().transpose().ok().flatten() {
-        if state.socket.lock().unwrap().is_none() {
-            state_atoms::ui_log(state, "info", "客户端已断开，上传中止");
-            return Ok(());
+    let mut merged: Vec<String> = Vec::new();
+    for rel in out {
+        if !merged.iter().any(|parent| rel.starts_with(&format!("{parent}/"))) {
+            merged.push(rel);
         }
+    }
+    Ok(merged)
+}
 
-        let abs = entry.path().to_path_buf();
-        if abs == base {
-            continue;
+/// 在后台执行“上传全部”或只同步 `paths` 中的子路径（为空表示整个目录），同一时间只允许一个；
+/// 客户端 `sync_all`/`sync_path` 与服务端主动推送共用。
+pub fn start(state: &RuntimeState, paths: &[String], trigger: &str) -> Result<(), String> {
+    if state.socket.lock().unwrap().is_none() {
+        return Err("客户端未连接".to_string());
+    }
+    let scope = normalize_scope(&state.dir, paths)?;
+    let Some(running) = state_atoms::FlagGuard::try_set(&state.sync_running) else {
+        return Err("已有同步正在进行中".to_string());
+    };
+
+    state_atoms::ui_log(state, "info", trigger.to_string());
+    let state = state.clone();
+    tokio::spawn(async move {
+        let _running = running;
+        if let Err(err) = run(&state, &scope).await {
+            let label = atom_helper_messages::format_sync_scope(&scope);
+            state_atoms::ui_log(&state, "error", format!("{label}失败: {err:#}"));
+            socket_emit::emit_sync_error(&state, err.to_string());
         }
+    });
+    Ok(())
+}
+
+/// 会走到 `broadcast_file` 的文件数（含因大小限制被跳过的），用于进度显示。
+fn count_files(totals: &TreeTotals) -> u32 {
+    (totals.files + totals.too_large) as u32
+}
+
+fn report_progress(state: &RuntimeState, done: u32, total: u32, label: &str) {
+    let _ = state.ui_tx.send(UiEvent::SyncProgress { done, total });
+    if let Some(line) = atom_helper_messages::format_chunk_progress(done, total, label, false) {
+        state_atoms::ui_log(state, "info", line);
+    }
+}
 
-        let Some(rel) = crate::watcher::normalize_rel_path(&base, &abs) else { continue };
-        if crate::watcher::should_ignore_rel(&rel) {
+/// 子路径所在的上级目录：任一命中过滤规则则跳过整个子路径，否则先发送 `create_dir`。
+async fn prepare_scope_root(state: &RuntimeState, rel: &str) -> bool {
+    let ancestors = atom_helper_path::ancestors(rel);
+    if let Some(parent) = ancestors.iter().find(|p| state_atoms::should_filter_rel(state, p)) {
+        socket_emit::send_server_warning(
+            state,
+            format!("sync_path -> {rel}"),
+            format!("上级目录 {parent} 匹配过滤规则，已跳过"),
+        );
+        return false;
+    }
+    for parent in ancestors {
+        shared_broadcast_entry::broadcast_create_dir(state, parent).await;
+    }
+    true
+}
+
+pub async fn run(state: &RuntimeState, scope: &[String]) -> Result<()> {
+    // 只为发起时的连接同步：连接被替换或断开后即中止（会话恢复也不续传）
+    let generation = state_atoms::connection_generation(state);
+    if !state_atoms::is_same_connection(state, generation) {
+        return Ok(());
+    }
+
+    let label = atom_helper_messages::format_sync_scope(scope);
+    let totals = tree_quota::measure(state, scope).await?;
+    if let Err(reason) = tree_quota::check(state, &totals) {
+        anyhow::bail!(reason);
+    }
+    let total = count_files(&totals);
+    state_atoms::ui_log(state, "info", format!("开始{label}...（共 {total} 个文件）"));
+    socket_emit::emit_sync_control(state, "sync_start", scope);
+
+    let base = state.dir.clone();
+    let roots: Vec<PathBuf> = if scope.is_empty() {
+        vec![base.clone()]
+    } else {
+        scope.iter().map(|rel| base.join(rel)).collect()
+    };
+    let mut done = 0u32;
+    for (i, root) in roots.iter().enumerate() {
+        if !scope.is_empty() && !prepare_scope_root(state, &scope[i]).await {
             continue;
         }
 
-        let rel = rel.replace('\\', "/");
-        let is_dir = entry.file_type().is_dir();
-        if state_atoms::should_filter_rel(state, &rel) {
-            let action = if is_dir { "create_dir" } else { "update" };
-            socket_emit::send_server_warning(
-                state,
-                format!("{action} -> {rel}"),
-                "匹配过滤规则，已跳过".to_string(),
-            );
-            if is_dir {
-                it.skip_current_dir();
+        let mut it = WalkDir::new(root).into_iter();
+        while let Some(entry) = it.next().transpose().ok().flatten() {
+            if !state_atoms::is_same_connection(state, generation) {
+                state_atoms::ui_log(state, "info", format!("客户端已断开或被替换，{label}中止"));
+                return Ok(());
             }
-            continue;
-        }
 
-        if is_dir {
-            shared_broadcast_entry::broadcast_create_dir(state, &rel).await;
-        } else if let Err(err) = shared_broadcast_entry::broadcast_file(state, &rel, &abs).await {
-            warn!("发送失败: {rel}: {err:#}");
+            let abs = entry.path().to_path_buf();
+            if abs == base {
+                continue;
+            }
+
+            let Some(rel) = crate::watcher::normalize_rel_path(&base, &abs) else { continue };
+            if crate::watcher::should_ignore_rel(&rel) {
+                continue;
+            }
+
+            let rel = rel.replace('\\', "/");
+            let is_dir = entry.file_type().is_dir();
+            if state_atoms::should_filter_rel(state, &rel) {
+                let action = if is_dir { "create_dir" } else { "update" };
+                socket_emit::send_server_warning(
+                    state,
+                    format!("{action} -> {rel}"),
+                    "匹配过滤规则，已跳过".to_string(),
+                );
+                if is_dir {
+                    it.skip_current_dir();
+                }
+                continue;
+            }
+
+            if is_dir {
+                shared_broadcast_entry::broadcast_create_dir(state, &rel).await;
+                continue;
+            }
+            if let Err(err) = shared_broadcast_entry::broadcast_file(state, &rel, &abs).await {
+                warn!("发送失败: {rel}: {err:#}");
+            }
+            done = done.saturating_add(1);
+            report_progress(state, done, total.max(done), &label);
         }
     }
 
-    if state.socket.lock().unwrap().is_some() {
-        socket_emit::emit_sync_control(state, "sync_complete");
-        state_atoms::ui_log(state, "info", "上传全部完成");
+    if state_atoms::is_same_connection(state, generation) {
+        socket_emit::emit_sync_control(state, "sync_complete", scope);
+        state_atoms::ui_log(state, "info", format!("{label}完成"));
     } else {
-        state_atoms::ui_log(state, "info", "客户端已断开，上传中止");
+        state_atoms::ui_log(state, "info", format!("客户端已断开或被替换，{label}中止"));
     }
     Ok(())
 }
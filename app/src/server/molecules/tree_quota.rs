@@ -0,0 +1,80 @@
+use crate::server::atoms::{atom_helper_limits, state as state_atoms};
+use crate::server::molecules::path_check::{PathChecker, TreeTotals};
+use crate::server::{RuntimeState, UiEvent};
+use anyhow::Result;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// 目录有变化后重新统计总量的间隔
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+pub fn enabled(state: &RuntimeState) -> bool {
+    let cfg = state.cfg.lock().unwrap();
+    cfg.quota_bytes.is_some() || cfg.quota_files.is_some()
+}
+
+/// 按实时规则统计 `scope`（为空表示整个同步目录）中会发送的文件数与字节数，只计数不读取内容；
+/// 设置了配额时一旦超出即停止遍历。统计整个目录时同时更新 GUI 显示。
+pub async fn measure(state: &RuntimeState, scope: &[String]) -> Result<TreeTotals> {
+    let totals = {
+        let state = state.clone();
+        let scope = scope.to_vec();
+        tokio::task::spawn_blocking(move || {
+            let cfg = state_atoms::server_config(&state);
+            let quota = cfg.quota_bytes.is_some() || cfg.quota_files.is_some();
+            let over = |files, bytes| quota && atom_helper_limits::check_quota(files, bytes, &cfg).is_err();
+            PathChecker::from_state(&state).count_scoped(&state.dir, &scope, over)
+        })
+        .await?
+    };
+    if scope.is_empty() {
+        state.tree_size_dirty.store(false, Ordering::SeqCst);
+        report(state, &totals);
+    }
+    Ok(totals)
+}
+
+pub fn check(state: &RuntimeState, totals: &TreeTotals) -> Result<(), String> {
+    atom_helper_limits::check_quota(totals.files, totals.bytes, &state_atoms::server_config(state))
+        .map_err(|reason| if totals.truncated { format!("{reason}（超出配额后停止统计，实际更多）") } else { reason })
+}
+
+fn report(state: &RuntimeState, totals: &TreeTotals) {
+    let _ = state.ui_tx.send(UiEvent::TreeSize {
+        files: totals.files,
+        bytes: totals.bytes,
+        over_quota: check(state, totals).is_err(),
+    });
+}
+
+/// 启动时统计一次同步目录总量，之后目录或规则有变化时按 `REFRESH_INTERVAL` 重新统计；服务停止时由调用方 abort。
+/// 只在设置了配额、或 GUI 需要显示总量（`show_size`）时统计。
+pub fn spawn_monitor(state: &RuntimeState, show_size: bool) -> JoinHandle<()> {
+    let state = state.clone();
+    tokio::spawn(async move {
+        if show_size || enabled(&state) {
+            match measure(&state, &[]).await {
+                Ok(totals) => {
+                    let line = format!(
+                        "同步目录共 {}{} 个待同步文件，{}",
+                        if totals.truncated { "至少 " } else { "" },
+                        totals.files,
+                        atom_helper_limits::human_bytes(totals.bytes)
+                    );
+                    state_atoms::ui_log(&state, "info", line);
+                    if let Err(reason) = check(&state, &totals) {
+                        state_atoms::ui_log(&state, "warn", reason);
+                    }
+                }
+                Err(err) => state_atoms::ui_log(&state, "warn", format!("统计同步目录失败: {err:#}")),
+            }
+        }
+        loop {
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+            if (show_size || enabled(&state)) && state.tree_size_dirty.load(Ordering::SeqCst) {
+                let _ = measure(&state, &[]).await;
+            }
+        }
+    })
+}
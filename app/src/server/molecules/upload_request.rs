@@ -0,0 +1,191 @@
+use crate::proto::UploadSession;
+use crate::server::atoms::state::{self as state_atoms, FlagGuard};
+use crate::server::atoms::{snapshot, socket_emit};
+use crate::server::molecules::path_check::PathChecker;
+use crate::server::RuntimeState;
+use std::time::{Duration, Instant};
+
+const SNAPSHOT_KEEP: usize = 5;
+const PROGRESS_EVERY: u32 = 20;
+
+/// 服务端请求客户端“上传全部”：先为同步目录拍快照（失败则不发请求），再 emit `request_upload`。
+pub fn request(state: &RuntimeState) -> Result<(), &'static str> {
+    if state.socket.lock().unwrap().is_none() {
+        return Err("客户端未连接");
+    }
+    let requesting = begin(state)?;
+
+    state_atoms::ui_log(state, "info", "正在为同步目录创建快照...");
+    let state = state.clone();
+    tokio::spawn(async move {
+        let _requesting = requesting;
+        let src = state.dir.clone();
+        let root = crate::settings::config_base_dir().join("snapshots");
+        // 与同步相同的实时过滤规则与大小限制
+        let checker = PathChecker::from_state(&state);
+        let res = tokio::task::spawn_blocking(move || {
+            snapshot::snapshot_dir(&src, &root, SNAPSHOT_KEEP, |rel, is_dir, size| {
+                !checker.check(rel, is_dir, size).is_skipped()
+            })
+        })
+        .await;
+        let snapshot = match res {
+            Ok(Ok((path, files))) => {
+                state_atoms::ui_log(&state, "info", format!("快照完成（{files} 个文件）: {}", path.display()));
+                path
+            }
+            Ok(Err(err)) => {
+                state_atoms::ui_log(&state, "error", format!("创建快照失败，已取消上传请求: {err:#}"));
+                return;
+            }
+            Err(err) => {
+                state_atoms::ui_log(&state, "error", format!("创建快照失败，已取消上传请求: {err}"));
+                return;
+            }
+        };
+
+        *state.pending_snapshot.lock().unwrap() = Some(snapshot);
+        socket_emit::emit_request_upload(&state);
+        state_atoms::ui_log(&state, "info", "已请求客户端上传全部文件");
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        // 超时后放弃这次请求，允许重新发起
+        let unanswered = state.pending_snapshot.lock().unwrap().take();
+        if let Some(path) = unanswered {
+            state_atoms::ui_log(
+                &state,
+                "warn",
+                format!(
+                    "客户端未响应上传请求（可能脚本版本过旧），请在手机上点击“上传全部”；上传前快照: {}",
+                    path.display()
+                ),
+            );
+        }
+    });
+    Ok(())
+}
+
+/// 重入检查：客户端正在上传，或已有请求在拍快照/等待客户端响应时拒绝；返回的标志在请求任务结束时清除。
+fn begin(state: &RuntimeState) -> Result<FlagGuard, &'static str> {
+    if state.upload_session.lock().unwrap().is_some() {
+        return Err("客户端正在上传");
+    }
+    if state.pending_snapshot.lock().unwrap().is_some() {
+        return Err("正在请求上传");
+    }
+    FlagGuard::try_set(&state.upload_requesting).ok_or("正在请求上传")
+}
+
+pub fn on_upload_start(state: &RuntimeState) {
+    let snapshot = state.pending_snapshot.lock().unwrap().take();
+    *state.upload_session.lock().unwrap() = Some(UploadSession {
+        started: Instant::now(),
+        files: 0,
+        dirs: 0,
+        bytes: 0,
+        snapshot,
+    });
+    state_atoms::ui_log(state, "info", "客户端开始上传全部文件...");
+}
+
+pub fn record_file(state: &RuntimeState, bytes: u64) {
+    let progress = {
+        let mut guard = state.upload_session.lock().unwrap();
+        let Some(session) = guard.as_mut() else { return };
+        session.files += 1;
+        session.bytes += bytes;
+        (session.files % PROGRESS_EVERY == 0).then_some((session.files, session.bytes))
+    };
+    if let Some((files, bytes)) = progress {
+        state_atoms::ui_log(
+            state,
+            "info",
+            format!("客户端上传中：已接收 {files} 个文件，共 {:.1}KB", bytes as f64 / 1024.0),
+        );
+    }
+}
+
+pub fn record_dir(state: &RuntimeState) {
+    if let Some(session) = state.upload_session.lock().unwrap().as_mut() {
+        session.dirs += 1;
+    }
+}
+
+pub fn on_upload_complete(state: &RuntimeState) {
+    let Some(session) = state.upload_session.lock().unwrap().take() else {
+        state_atoms::ui_log(state, "info", "客户端上传全部完成");
+        return;
+    };
+    let mut line = format!(
+        "客户端上传全部完成：{} 个文件、{} 个目录，共 {:.1}KB，用时 {:.1}s",
+        session.files,
+        session.dirs,
+        session.bytes as f64 / 1024.0,
+        session.started.elapsed().as_secs_f64()
+    );
+    if let Some(path) = &session.snapshot {
+        line.push_str(&format!("；上传前快照: {}", path.display()));
+    }
+    state_atoms::ui_log(state, "info", line);
+}
+
+/// 断开时若上传未完成，提示快照位置以便恢复。
+pub fn on_disconnect(state: &RuntimeState) {
+    state.pending_snapshot.lock().unwrap().take();
+    let Some(session) = state.upload_session.lock().unwrap().take() else { return };
+    let mut line = format!("客户端上传未完成即断开（已接收 {} 个文件）", session.files);
+    if let Some(path) = &session.snapshot {
+        line.push_str(&format!("，上传前快照: {}", path.display()));
+    }
+    state_atoms::ui_log(state, "warn", line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ServerConfig, TakeoverPolicy};
+    use std::path::PathBuf;
+    use tokio::sync::mpsc;
+
+    fn test_state() -> RuntimeState {
+        let cfg = ServerConfig {
+            host: "127.0.0.1".parse().unwrap(),
+            port: 0,
+            dir: std::env::temp_dir(),
+            server_side_path_regex: Vec::new(),
+            enable_server_side_file_size_limit: false,
+            max_server_side_file_size: 0,
+            size_rules: Vec::new(),
+            quota_bytes: None,
+            quota_files: None,
+            pair_url_template: None,
+            resume_grace_secs: 0,
+            takeover: TakeoverPolicy::Reject,
+            require_device_approval: false,
+            trusted_devices: Vec::new(),
+            device_rules: Default::default(),
+        };
+        let (ui_tx, _ui_rx) = mpsc::unbounded_channel();
+        RuntimeState::new(&cfg, ui_tx, Vec::new(), "token".to_string())
+    }
+
+    #[test]
+    fn request_without_client_is_refused() {
+        let state = test_state();
+        assert_eq!(request(&state).err(), Some("客户端未连接"));
+        assert!(!state.upload_requesting.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn second_request_is_refused_until_first_finishes() {
+        let state = test_state();
+        let first = begin(&state).expect("第一次请求应当被接受");
+        assert_eq!(begin(&state).err(), Some("正在请求上传"));
+        drop(first);
+        assert!(begin(&state).is_ok());
+
+        // 已发出请求、等待客户端响应
+        *state.pending_snapshot.lock().unwrap() = Some(PathBuf::from("snapshot"));
+        assert_eq!(begin(&state).err(), Some("正在请求上传"));
+    }
+}
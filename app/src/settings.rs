@@ -1,10 +1,107 @@
-use crate::config::{Cli, ServerConfig};
+use crate::config::{Cli, DeviceRules, KnownDevice, ServerConfig, SizeRule, TakeoverPolicy};
+use crate::server::atoms::{atom_helper_filter, token};
 use anyhow::{Context, Result};
 use clap::parser::ValueSource;
 use clap::ArgMatches;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::net::IpAddr;
 use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, RwLock};
+
+/// `--config` / `F11ESYNC_CONFIG` 指定的配置文件；指定后忽略便携模式与 profile。
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// 串行化配置文件的“读取—修改—写回”，避免后台线程同时写入时互相覆盖。
+static UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 当前 profile；`None` 表示默认配置（`config.yaml`）。
+static ACTIVE_PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// 可执行文件旁存在此文件时启用便携模式：配置与 profile 都保存在可执行文件所在目录。
+pub const PORTABLE_MARKER: &str = "f11esync.portable";
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+const ACTIVE_PROFILE_FILE: &str = "active_profile";
+
+/// 启动时确定配置文件：`--config` > `--profile` > 上次在 GUI 中选择的 profile > 默认配置。
+pub fn init_config_path(path: Option<&Path>, profile: Option<&str>) -> Result<()> {
+    if let Some(path) = path {
+        let _ = CONFIG_PATH_OVERRIDE.set(ensure_abs_dir(path));
+        return Ok(());
+    }
+
+    let remembered = std::fs::read_to_string(config_base_dir().join(ACTIVE_PROFILE_FILE)).ok();
+    let name = profile.or(remembered.as_deref().map(str::trim));
+    *ACTIVE_PROFILE.write().unwrap() = normalize_profile(name)?;
+    Ok(())
+}
+
+fn normalize_profile(name: Option<&str>) -> Result<Option<String>> {
+    let Some(name) = name.map(str::trim).filter(|n| !n.is_empty() && *n != DEFAULT_PROFILE) else {
+        return Ok(None);
+    };
+    let valid = name.len() <= 64
+        && !name.starts_with('.')
+        && !name.contains(['/', '\\', ':'])
+        && !name.chars().any(char::is_control);
+    if !valid {
+        anyhow::bail!("profile 名称不合法: {name}（不能包含路径分隔符或以 . 开头）");
+    }
+    Ok(Some(name.to_string()))
+}
+
+pub fn active_profile() -> String {
+    ACTIVE_PROFILE
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// 切换 profile 并记住选择（下次未指定 `--profile` 启动时沿用）。
+#[cfg(feature = "gui")]
+pub fn switch_profile(name: &str) -> Result<()> {
+    let normalized = normalize_profile(Some(name))?;
+    let base = config_base_dir();
+    std::fs::create_dir_all(&base).with_context(|| format!("创建配置目录失败: {}", base.display()))?;
+    let marker = base.join(ACTIVE_PROFILE_FILE);
+    std::fs::write(&marker, normalized.as_deref().unwrap_or(DEFAULT_PROFILE))
+        .with_context(|| format!("写入 profile 选择失败: {}", marker.display()))?;
+    *ACTIVE_PROFILE.write().unwrap() = normalized;
+    Ok(())
+}
+
+/// 已有的 profile 名称（总是包含 `default`）。
+pub fn list_profiles() -> Vec<String> {
+    let mut names = std::fs::read_dir(config_base_dir().join("profiles"))
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    let path = e.path();
+                    let is_yaml = path.extension().is_some_and(|ext| ext == "yaml");
+                    is_yaml.then(|| path.file_stem()?.to_str().map(str::to_string)).flatten()
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names.insert(0, DEFAULT_PROFILE.to_string());
+    names.dedup();
+    names
+}
+
+pub fn is_portable() -> bool {
+    portable_dir().is_some()
+}
+
+fn portable_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let dir = exe.parent()?;
+    dir.join(PORTABLE_MARKER).exists().then(|| dir.to_path_buf())
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -35,6 +132,15 @@ pub struct ServerConfigFile {
     pub server_side_path_regex: Vec<String>,
     pub enable_file_size_limit: bool,
     pub max_file_size: u64,
+    pub size_rules: Vec<SizeRule>,
+    pub quota_bytes: Option<u64>,
+    pub quota_files: Option<u64>,
+    pub pair_url_template: Option<String>,
+    pub resume_grace_secs: u64,
+    pub takeover: TakeoverPolicy,
+    pub require_device_approval: bool,
+    pub trusted_devices: Vec<KnownDevice>,
+    pub device_rules: BTreeMap<String, DeviceRules>,
 }
 
 impl Default for ServerConfigFile {
@@ -46,10 +152,22 @@ impl Default for ServerConfigFile {
             server_side_path_regex: vec![r"\.DS_Store$".to_string(), r"__MACOSX$".to_string()],
             enable_file_size_limit: false,
             max_file_size: 250 * 1024,
+            size_rules: Vec::new(),
+            quota_bytes: None,
+            quota_files: None,
+            pair_url_template: None,
+            resume_grace_secs: 60,
+            takeover: TakeoverPolicy::Reject,
+            require_device_approval: false,
+            trusted_devices: Vec::new(),
+            device_rules: BTreeMap::new(),
         }
     }
 }
 
+/// 当前配置文件结构版本；旧版本文件在 `load` 时按 `MIGRATIONS` 逐级升级。
+pub const SCHEMA_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct AppConfig {
@@ -61,7 +179,7 @@ pub struct AppConfig {
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
-            schema_version: 1,
+            schema_version: SCHEMA_VERSION,
             server: ServerConfigFile::default(),
             ui: UiConfig::default(),
         }
@@ -75,8 +193,51 @@ pub struct EffectiveConfig {
     pub ui: UiConfig,
 }
 
+/// 命令行或 `F11ESYNC_*` 环境变量显式指定（clap 已保证命令行优先于环境变量）。
 fn is_cli(matches: &ArgMatches, id: &str) -> bool {
-    matches.value_source(id) == Some(ValueSource::CommandLine)
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
+/// 列表型环境变量：每行一条，或 YAML 列表 `["a", "b"]`。
+fn env_list(name: &str) -> Option<Vec<String>> {
+    let raw = std::env::var(name).ok()?;
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    if trimmed.starts_with('[') {
+        if let Ok(list) = serde_yaml::from_str::<Vec<String>>(trimmed) {
+            return Some(list);
+        }
+    }
+    Some(
+        trimmed
+            .lines()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+fn env_path_regex() -> Option<Vec<String>> {
+    env_list("F11ESYNC_PATH_REGEX")
+}
+
+/// `F11ESYNC_SIZE_RULE`：格式同 `--size-rule`，无法解析的条目跳过并警告。
+fn env_size_rules() -> Option<Vec<SizeRule>> {
+    let rules = env_list("F11ESYNC_SIZE_RULE")?
+        .iter()
+        .filter_map(|raw| {
+            raw.parse::<SizeRule>()
+                .inspect_err(|err| tracing::warn!("忽略无效的 F11ESYNC_SIZE_RULE 条目 `{raw}`: {err}"))
+                .ok()
+        })
+        .collect();
+    Some(rules)
 }
 
 fn ensure_abs_dir(path: &Path) -> PathBuf {
@@ -93,27 +254,73 @@ fn canonicalize_best_effort(path: &Path) -> PathBuf {
 }
 
 pub fn config_file_path() -> PathBuf {
+    if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+        return path.clone();
+    }
+
+    let base = config_base_dir();
+    match ACTIVE_PROFILE.read().unwrap().as_deref() {
+        Some(name) => base.join("profiles").join(format!("{name}.yaml")),
+        None => base.join("config.yaml"),
+    }
+}
+
+/// HTTP 控制接口的口令文件，与当前配置文件同名（`config.yaml` -> `config.token`）。
+pub fn control_token_path() -> PathBuf {
+    config_file_path().with_extension("token")
+}
+
+/// 读取口令文件，不存在或为空时生成新口令并写入（Unix 下仅当前用户可读）。
+pub fn load_or_create_control_token() -> Result<String> {
+    let path = control_token_path();
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        let existing = existing.trim();
+        if !existing.is_empty() {
+            return Ok(existing.to_string());
+        }
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("创建配置目录失败: {}", parent.display()))?;
+    }
+    let fresh = token::random_token()?;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(&path)
+        .with_context(|| format!("写入控制口令失败: {}", path.display()))?;
+    std::io::Write::write_all(&mut file, fresh.as_bytes())
+        .with_context(|| format!("写入控制口令失败: {}", path.display()))?;
+    Ok(fresh)
+}
+
+/// 配置根目录：便携模式下为可执行文件所在目录，否则为平台默认位置。
+pub fn config_base_dir() -> PathBuf {
+    if let Some(dir) = portable_dir() {
+        return dir;
+    }
+
     #[cfg(target_os = "macos")]
     {
         let home = std::env::var_os("HOME").unwrap_or_default();
         return PathBuf::from(home)
             .join("Library")
             .join("Application Support")
-            .join("F11eSync")
-            .join("config.yaml");
+            .join("F11eSync");
     }
 
     #[cfg(target_os = "windows")]
     {
         if let Some(appdata) = std::env::var_os("APPDATA") {
-            return PathBuf::from(appdata).join("F11eSync").join("config.yaml");
+            return PathBuf::from(appdata).join("F11eSync");
         }
         let home = std::env::var_os("USERPROFILE").unwrap_or_default();
         return PathBuf::from(home)
             .join("AppData")
             .join("Roaming")
-            .join("F11eSync")
-            .join("config.yaml");
+            .join("F11eSync");
     }
 
     #[cfg(not(any(target_os = "macos", target_os = "windows")))]
@@ -122,8 +329,61 @@ pub fn config_file_path() -> PathBuf {
             .map(PathBuf::from)
             .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
             .unwrap_or_else(|| PathBuf::from("."));
-        return base.join("f11esync").join("config.yaml");
+        base.join("f11esync")
+    }
+}
+
+type Migration = fn(&mut serde_yaml::Mapping) -> Result<()>;
+
+/// `(from_version, migration)`：把 `from_version` 的文件升级到 `from_version + 1`。
+const MIGRATIONS: &[(u32, Migration)] = &[(0, migrate_v0_to_v1)];
+
+/// v0：早期未写入 `schema_version` 的文件，字段与 v1 一致，只需补版本号。
+fn migrate_v0_to_v1(_map: &mut serde_yaml::Mapping) -> Result<()> {
+    Ok(())
+}
+
+fn file_schema_version(map: &serde_yaml::Mapping) -> Result<u32> {
+    match map.get("schema_version") {
+        None | Some(serde_yaml::Value::Null) => Ok(0),
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .with_context(|| format!("schema_version 不是合法的版本号: {v:?}")),
+    }
+}
+
+/// 把原始 YAML 升级到 `SCHEMA_VERSION`，返回升级前的版本号。
+fn migrate(root: &mut serde_yaml::Value) -> Result<u32> {
+    if root.is_null() {
+        *root = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
     }
+    let serde_yaml::Value::Mapping(map) = root else {
+        anyhow::bail!("配置文件顶层必须是键值映射");
+    };
+
+    let from = file_schema_version(map)?;
+    if from > SCHEMA_VERSION {
+        anyhow::bail!("配置文件版本 {from} 高于当前程序支持的版本 {SCHEMA_VERSION}，请升级 F11eSync");
+    }
+
+    let mut version = from;
+    while version < SCHEMA_VERSION {
+        let (_, step) = MIGRATIONS
+            .iter()
+            .find(|(v, _)| *v == version)
+            .with_context(|| format!("缺少配置迁移: v{version} -> v{}", version + 1))?;
+        step(map).with_context(|| format!("配置迁移失败: v{version} -> v{}", version + 1))?;
+        version += 1;
+        map.insert("schema_version".into(), version.into());
+    }
+    Ok(from)
+}
+
+fn backup_path(path: &Path, tag: &str) -> PathBuf {
+    let mut s = path.as_os_str().to_os_string();
+    s.push(format!(".{tag}.bak"));
+    PathBuf::from(s)
 }
 
 pub fn load() -> Result<Option<AppConfig>> {
@@ -133,12 +393,45 @@ pub fn load() -> Result<Option<AppConfig>> {
     }
 
     let bytes = std::fs::read(&path).with_context(|| format!("读取配置失败: {}", path.display()))?;
-    let cfg: AppConfig =
+    let mut root: serde_yaml::Value =
         serde_yaml::from_slice(&bytes).with_context(|| format!("解析配置失败: {}", path.display()))?;
+    let from = migrate(&mut root).with_context(|| format!("升级配置失败: {}", path.display()))?;
+    // 未迁移时直接按原文解析，错误信息能带上行列号
+    let parsed = if from == SCHEMA_VERSION {
+        serde_yaml::from_slice::<AppConfig>(&bytes)
+    } else {
+        serde_yaml::from_value::<AppConfig>(root)
+    };
+    let cfg = parsed.with_context(|| format!("解析配置失败: {}", path.display()))?;
+
+    if from != SCHEMA_VERSION {
+        let backup = backup_path(&path, &format!("v{from}"));
+        std::fs::write(&backup, &bytes)
+            .with_context(|| format!("备份旧版本配置失败: {}", backup.display()))?;
+        save(&cfg)?;
+        tracing::info!("配置已从 v{from} 升级到 v{SCHEMA_VERSION}，原文件备份于 {}", backup.display());
+    }
     Ok(Some(cfg))
 }
 
+/// GUI 启动用：解析失败时不静默回退，而是先把原文件备份（避免之后自动保存把它覆盖），
+/// 再返回可直接展示给用户的错误信息。
 #[cfg(feature = "gui")]
+pub fn load_or_backup_invalid() -> (Option<AppConfig>, Option<String>) {
+    match load() {
+        Ok(cfg) => (cfg, None),
+        Err(err) => {
+            let path = config_file_path();
+            let backup = backup_path(&path, "invalid");
+            let note = match std::fs::copy(&path, &backup) {
+                Ok(_) => format!("原文件已备份到 {}", backup.display()),
+                Err(copy_err) => format!("备份原文件失败: {copy_err}"),
+            };
+            (None, Some(format!("{err:#}；{note}，当前使用默认配置")))
+        }
+    }
+}
+
 pub fn save(cfg: &AppConfig) -> Result<()> {
     let path = config_file_path();
     if let Some(parent) = path.parent() {
@@ -154,7 +447,33 @@ pub fn save(cfg: &AppConfig) -> Result<()> {
     Ok(())
 }
 
-pub fn merge(cli: &Cli, matches: &ArgMatches, file: Option<AppConfig>) -> EffectiveConfig {
+/// 把设备加入配置文件中的 `trusted_devices`（已存在时只更新名称），其他配置保持不变。
+pub fn trust_device(device: &KnownDevice) -> Result<()> {
+    update(|cfg| {
+        let list = &mut cfg.server.trusted_devices;
+        match list.iter_mut().find(|d| d.id == device.id) {
+            Some(known) => known.name = device.name.clone(),
+            None => list.push(device.clone()),
+        }
+    })
+}
+
+/// 写入配置文件中某台设备的 `device_rules`，其他配置保持不变。
+pub fn store_device_rules(id: &str, rules: &DeviceRules) -> Result<()> {
+    update(|cfg| {
+        cfg.server.device_rules.insert(id.to_string(), rules.clone());
+    })
+}
+
+fn update(change: impl FnOnce(&mut AppConfig)) -> Result<()> {
+    let _guard = UPDATE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut cfg = load()?.unwrap_or_default();
+    change(&mut cfg);
+    save(&cfg)
+}
+
+/// 在配置文件之上叠加环境变量与命令行参数，返回仍为落盘结构的 `AppConfig`（供 `config show` 使用）。
+pub fn merge_file(cli: &Cli, matches: &ArgMatches, file: Option<AppConfig>) -> AppConfig {
     let mut base = file.unwrap_or_default();
 
     // Ensure directory is absolute even when loaded from disk.
@@ -173,6 +492,8 @@ pub fn merge(cli: &Cli, matches: &ArgMatches, file: Option<AppConfig>) -> Effect
     }
     if is_cli(matches, "path_regex") && !cli.path_regex.is_empty() {
         base.server.server_side_path_regex = cli.path_regex.clone();
+    } else if let Some(list) = env_path_regex() {
+        base.server.server_side_path_regex = list;
     }
     if is_cli(matches, "enable_file_size_limit") {
         base.server.enable_file_size_limit = cli.enable_file_size_limit;
@@ -180,6 +501,34 @@ pub fn merge(cli: &Cli, matches: &ArgMatches, file: Option<AppConfig>) -> Effect
     if is_cli(matches, "max_file_size") {
         base.server.max_file_size = cli.max_file_size;
     }
+    if is_cli(matches, "size_rule") && !cli.size_rule.is_empty() {
+        base.server.size_rules = cli.size_rule.clone();
+    } else if let Some(rules) = env_size_rules() {
+        base.server.size_rules = rules;
+    }
+    if is_cli(matches, "quota_bytes") {
+        base.server.quota_bytes = cli.quota_bytes;
+    }
+    if is_cli(matches, "quota_files") {
+        base.server.quota_files = cli.quota_files;
+    }
+    if is_cli(matches, "pair_url") {
+        base.server.pair_url_template = cli.pair_url.clone();
+    }
+    if is_cli(matches, "resume_grace") {
+        base.server.resume_grace_secs = cli.resume_grace;
+    }
+    if is_cli(matches, "takeover") {
+        base.server.takeover = cli.takeover;
+    }
+    if is_cli(matches, "require_approval") {
+        base.server.require_device_approval = cli.require_approval;
+    }
+    base
+}
+
+pub fn merge(cli: &Cli, matches: &ArgMatches, file: Option<AppConfig>) -> EffectiveConfig {
+    let base = merge_file(cli, matches, file);
 
     let server = ServerConfig {
         host: base.server.host,
@@ -188,6 +537,15 @@ pub fn merge(cli: &Cli, matches: &ArgMatches, file: Option<AppConfig>) -> Effect
         server_side_path_regex: base.server.server_side_path_regex.clone(),
         enable_server_side_file_size_limit: base.server.enable_file_size_limit,
         max_server_side_file_size: base.server.max_file_size,
+        size_rules: base.server.size_rules.clone(),
+        quota_bytes: base.server.quota_bytes,
+        quota_files: base.server.quota_files,
+        pair_url_template: base.server.pair_url_template.clone(),
+        resume_grace_secs: base.server.resume_grace_secs,
+        takeover: base.server.takeover,
+        require_device_approval: base.server.require_device_approval,
+        trusted_devices: base.server.trusted_devices.clone(),
+        device_rules: base.server.device_rules.clone(),
     };
 
     EffectiveConfig {
@@ -196,3 +554,63 @@ pub fn merge(cli: &Cli, matches: &ArgMatches, file: Option<AppConfig>) -> Effect
         ui: base.ui,
     }
 }
+
+fn value_slot<'a>(root: &'a mut serde_yaml::Value, key: &str) -> Result<&'a mut serde_yaml::Value> {
+    let mut slot = root;
+    for part in key.split('.') {
+        let serde_yaml::Value::Mapping(map) = slot else {
+            anyhow::bail!("未知配置项: {key}");
+        };
+        slot = map
+            .get_mut(part)
+            .with_context(|| format!("未知配置项: {key}"))?;
+    }
+    if slot.is_mapping() {
+        anyhow::bail!("{key} 不是单个配置项，请指定具体字段");
+    }
+    Ok(slot)
+}
+
+/// 按点分路径（如 `server.port`）修改配置项；值按 YAML 标量/序列解析，类型不符时退回按字符串解析。
+#[cfg_attr(feature = "gui", allow(dead_code))]
+pub fn set_value(cfg: &AppConfig, key: &str, raw: &str) -> Result<AppConfig> {
+    let root = serde_yaml::to_value(cfg).context("序列化配置失败")?;
+
+    let parsed: serde_yaml::Value =
+        serde_yaml::from_str(raw).unwrap_or_else(|_| serde_yaml::Value::String(raw.to_string()));
+    let mut typed = root.clone();
+    *value_slot(&mut typed, key)? = parsed;
+    if let Ok(next) = serde_yaml::from_value::<AppConfig>(typed) {
+        return Ok(next);
+    }
+
+    let mut as_string = root;
+    *value_slot(&mut as_string, key)? = serde_yaml::Value::String(raw.to_string());
+    serde_yaml::from_value::<AppConfig>(as_string).with_context(|| format!("配置项 {key} 的值不合法: {raw}"))
+}
+
+/// 逐条编译过滤正则，返回带序号与错误原因的问题描述。
+#[cfg_attr(feature = "gui", allow(dead_code))]
+pub fn regex_issues(patterns: &[String]) -> Vec<String> {
+    atom_helper_filter::invalid_regexes(patterns)
+        .into_iter()
+        .map(|(n, pattern, err)| format!("server_side_path_regex 第 {n} 条 `{pattern}` 不合法: {err}"))
+        .collect()
+}
+
+/// 校验配置：过滤正则、同步目录、端口。返回所有问题（空表示通过）。
+#[cfg_attr(feature = "gui", allow(dead_code))]
+pub fn validate(cfg: &AppConfig) -> Vec<String> {
+    let mut issues = regex_issues(&cfg.server.server_side_path_regex);
+
+    let dir = &cfg.server.dir;
+    if dir.exists() && !dir.is_dir() {
+        issues.push(format!("dir 不是目录: {}", dir.display()));
+    }
+
+    if cfg.server.port == 0 {
+        issues.push("port 不能为 0".to_string());
+    }
+
+    issues
+}
@@ -58,7 +58,7 @@ fn default_download_dir() -> Option<PathBuf> {
     #[cfg(not(target_os = "windows"))]
     {
         let home = std::env::var_os("HOME").map(PathBuf::from)?;
-        return Some(home.join("Downloads"));
+        Some(home.join("Downloads"))
     }
 }
 
@@ -135,6 +135,7 @@ pub async fn check_update_message(silent: bool) -> Result<String> {
     }
 }
 
+#[cfg_attr(feature = "gui", allow(dead_code))]
 pub async fn check_update(silent: bool) -> Result<()> {
     let msg = match check_update_message(silent).await {
         Ok(message) => message,
@@ -46,8 +46,14 @@ pub fn normalize_rel_path(dir: &Path, abs_path: &Path) -> Option<String> {
     Some(rel.replace('\\', "/"))
 }
 
+/// 压缩上传在解压完成前使用的临时文件后缀，监控与同步均忽略
+pub const UPLOAD_TMP_SUFFIX: &str = ".f11esync-upload";
+
 pub fn should_ignore_rel(rel: &str) -> bool {
-    rel == ".DS_Store" || rel.ends_with("/.DS_Store") || rel.ends_with(".DS_Store")
+    rel == ".DS_Store"
+        || rel.ends_with("/.DS_Store")
+        || rel.ends_with(".DS_Store")
+        || rel.ends_with(UPLOAD_TMP_SUFFIX)
 }
 
 pub fn start_watcher(
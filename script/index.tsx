@@ -36,6 +36,23 @@ const Main = () => {
 
   // 处理文件同步事件
   const onMessage = async (event: FileChangeEvent) => {
+    // 服务端请求上传全部（闭包中的 connected 可能已过期，以 socketRef 为准）
+    if (event.action === 'request_upload') {
+      if (uploadingRef.current) return
+      addLog('上传全部', '服务端请求', 'success')
+      await uploadAllFiles({
+        socketRef,
+        targetPathRef,
+        chunkAckWaitersRef,
+        settings,
+        addLog,
+        setUploading,
+        uploadingRef,
+        setErrorMessage,
+        connected: socketRef.current !== null
+      })
+      return
+    }
     await handleFileSync(event, {
       uploadingRef,
       targetPathRef,
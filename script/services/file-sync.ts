@@ -2,6 +2,28 @@ import { Path } from 'scripting'
 import type { FileChangeEvent, ChunkReceiveState, ChunkAckWaiter, SyncLog } from '../types'
 import type { Settings } from '../utils'
 import { shouldFilterPath } from '../utils'
+import { peerSupports } from './socket-client'
+
+/**
+ * 按 encoding 解码文件内容：utf8 为纯文本，binary 为二进制附件，未提供时为 base64
+ */
+const decodeContent = (content: string, encoding?: string, binary?: Data): Data => {
+    if (encoding === 'binary') {
+        if (!binary) throw new Error('缺少二进制附件')
+        return binary
+    }
+    if (encoding === 'utf8') {
+        const data = Data.fromRawString(content)
+        if (!data) throw new Error('UTF-8 编码失败')
+        return data
+    }
+    if (encoding && encoding !== 'base64') {
+        throw new Error(`不支持的编码: ${encoding}`)
+    }
+    const data = Data.fromBase64String(content)
+    if (!data) throw new Error('Base64 解码失败')
+    return data
+}
 
 /**
  * 处理文件同步事件
@@ -28,15 +50,24 @@ export const handleFileSync = async (
     }
 
     // 处理同步控制消息
+    // scope 存在时为 sync_path 的部分同步
     if (event.action === 'sync_start') {
         setSyncing(true)
-        addLog('下载全部', '开始', 'success', '正在同步所有文件...')
+        if (event.scope?.length) {
+            addLog('下载部分', '开始', 'success', `正在同步: ${event.scope.join(', ')}`)
+        } else {
+            addLog('下载全部', '开始', 'success', '正在同步所有文件...')
+        }
         return
     }
 
     if (event.action === 'sync_complete') {
         setTimeout(() => { setSyncing(false) }, 100)
-        addLog('下载全部', '完成', 'success', '所有文件已同步完成')
+        if (event.scope?.length) {
+            addLog('下载部分', '完成', 'success', `已同步: ${event.scope.join(', ')}`)
+        } else {
+            addLog('下载全部', '完成', 'success', '所有文件已同步完成')
+        }
         return
     }
 
@@ -46,6 +77,25 @@ export const handleFileSync = async (
         return
     }
 
+    if (event.action === 'dir_listing') {
+        if (event.error) {
+            addLog('浏览', event.path || '/', 'error', event.error)
+        } else {
+            addLog('浏览', event.path || '/', 'success', `${event.entries?.length ?? 0} 项`)
+        }
+        return
+    }
+
+    if (event.action === 'hello') {
+        addLog(event.resumed ? '恢复会话' : '握手', `服务端 ${event.serverVersion ?? '未知'}`, 'success', `能力: ${event.negotiated?.join(', ') || '无'}`)
+        return
+    }
+
+    if (event.action === 'fetch_error') {
+        addLog('获取文件', event.path, 'error', event.content || '未知错误')
+        return
+    }
+
     // 处理分片开始
     if (event.action === 'chunk_start' && event.fileId) {
         const currentTargetPath = targetPathRef.current
@@ -62,7 +112,8 @@ export const handleFileSync = async (
         chunkReceiveStateRef.current.set(event.fileId, {
             path: fullPath,
             receivedChunks: 0,
-            totalChunks: event.totalChunks || 0
+            totalChunks: event.totalChunks || 0,
+            encoding: event.encoding
         })
 
         addLog('开始接收', event.path, 'success', `文件大小: ${((event.totalSize || 0) / 1024).toFixed(1)}KB, 分片数: ${event.totalChunks}`)
@@ -70,16 +121,23 @@ export const handleFileSync = async (
     }
 
     // 处理分片数据
-    if (event.action === 'chunk_data' && event.fileId && event.content) {
+    if (event.action === 'chunk_data' && event.fileId && (event.content || event.binary)) {
         const state = chunkReceiveStateRef.current.get(event.fileId)
         if (!state) {
             console.error('收到分片但未找到接收状态:', event.fileId)
             return
         }
 
+        const replyChunkAck = (result: { success: boolean, error?: string }) => {
+            if (event.ack) {
+                event.ack(result)
+            } else if (socketRef.current) {
+                socketRef.current.emit('chunk_ack', { fileId: event.fileId, chunkIndex: event.chunkIndex, ...result })
+            }
+        }
+
         try {
-            const data = Data.fromBase64String(event.content)
-            if (!data) throw new Error('Base64 解码失败')
+            const data = decodeContent(event.content ?? '', state.encoding, event.binary)
 
             if (event.chunkIndex === 0) {
                 await FileManager.writeAsData(state.path, data)
@@ -89,13 +147,7 @@ export const handleFileSync = async (
 
             state.receivedChunks++
 
-            if (socketRef.current) {
-                socketRef.current.emit('chunk_ack', {
-                    fileId: event.fileId,
-                    chunkIndex: event.chunkIndex,
-                    success: true
-                })
-            }
+            replyChunkAck({ success: true })
 
             // 按百分比输出日志（每20%或最后一片）
             const progress = Math.floor((state.receivedChunks / state.totalChunks) * 5)
@@ -104,14 +156,7 @@ export const handleFileSync = async (
                 addLog('接收分片', event.path || '未知文件', 'success', `${state.receivedChunks}/${state.totalChunks} (${Math.round((state.receivedChunks / state.totalChunks) * 100)}%)`)
             }
         } catch (error) {
-            if (socketRef.current) {
-                socketRef.current.emit('chunk_ack', {
-                    fileId: event.fileId,
-                    chunkIndex: event.chunkIndex,
-                    success: false,
-                    error: error instanceof Error ? error.message : String(error)
-                })
-            }
+            replyChunkAck({ success: false, error: error instanceof Error ? error.message : String(error) })
             const errorMsg = error instanceof Error ? error.message : String(error)
             addLog('接收分片', event.path || '未知文件', 'error', `分片 ${event.chunkIndex} 失败: ${errorMsg}`)
         }
@@ -173,18 +218,13 @@ export const handleFileSync = async (
                 await FileManager.createDirectory(fullPath, true)
             }
         } else if (event.action === 'update') {
-            if (event.content !== null) {
+            if (event.content !== null || event.binary) {
                 addLog('更新文件', event.path, 'success')
                 const parentDir = Path.dirname(fullPath)
                 if (!await FileManager.exists(parentDir)) {
                     await FileManager.createDirectory(parentDir, true)
                 }
-                const data = Data.fromBase64String(event.content)
-                if (data) {
-                    await FileManager.writeAsData(fullPath, data)
-                } else {
-                    throw new Error('Base64 解码失败')
-                }
+                await FileManager.writeAsData(fullPath, decodeContent(event.content ?? '', event.encoding, event.binary))
             }
         }
     } catch (error) {
@@ -233,6 +273,7 @@ export const uploadAllFiles = async (
 
     try {
         socketRef.current.emit('client_upload_start', {})
+        const useBinary = peerSupports('binary')
 
         const scanAndSend = async (dirPath: string, relPath: string) => {
             // 检查连接状态
@@ -271,10 +312,14 @@ export const uploadAllFiles = async (
                         continue
                     }
 
+                    // 协商了 binary 时以二进制附件发送原始字节（空文件没有附件，仍用 base64），否则发送 base64 文本
                     const data = await FileManager.readAsData(itemPath)
-                    const content = data.toBase64String()
+                    const asBinary = useBinary && data.size > 0
+                    const encoding = asBinary ? 'binary' : 'base64'
+                    const content = asBinary ? '' : data.toBase64String()
+                    const payloadSize = asBinary ? data.size : content.length
                     const CHUNK_SIZE = 256 * 1024
-                    const needsChunking = content.length > CHUNK_SIZE
+                    const needsChunking = payloadSize > CHUNK_SIZE
 
                     if (needsChunking) {
                         let fileSuccess = false
@@ -285,7 +330,7 @@ export const uploadAllFiles = async (
                             }
 
                             const fileId = `${Date.now()}-${Math.random().toString(36).substr(2, 9)}`
-                            const totalChunks = Math.ceil(content.length / CHUNK_SIZE)
+                            const totalChunks = Math.ceil(payloadSize / CHUNK_SIZE)
 
                             try {
                                 socketRef.current?.emit('chunk_start', {
@@ -293,7 +338,8 @@ export const uploadAllFiles = async (
                                     fileId,
                                     totalChunks,
                                     totalSize: stat.size,
-                                    isDir: false
+                                    isDir: false,
+                                    encoding
                                 })
 
                                 addLog('开始发送', itemRelPath, 'success', `${totalChunks} 个分片`)
@@ -306,13 +352,16 @@ export const uploadAllFiles = async (
                                     }
 
                                     const start = chunkIndex * CHUNK_SIZE
-                                    const end = Math.min(start + CHUNK_SIZE, content.length)
-                                    const chunkContent = content.substring(start, end)
+                                    const end = Math.min(start + CHUNK_SIZE, payloadSize)
 
-                                    socketRef.current?.emit('chunk_data', {
+                                    socketRef.current?.emit('chunk_data', asBinary ? {
+                                        fileId,
+                                        chunkIndex,
+                                        bytes: data.slice(start, end)
+                                    } : {
                                         fileId,
                                         chunkIndex,
-                                        content: chunkContent
+                                        content: content.substring(start, end)
                                     })
 
                                     const success = await new Promise<boolean>((resolve) => {
@@ -357,8 +406,9 @@ export const uploadAllFiles = async (
                         socketRef.current?.emit('update', {
                             path: itemRelPath,
                             content,
-                            encoding: 'base64',
-                            isDir: false
+                            encoding,
+                            isDir: false,
+                            ...(asBinary ? { bytes: data } : {})
                         })
                         addLog('上传文件', itemRelPath, 'success')
                     }
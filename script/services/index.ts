@@ -1,2 +1,2 @@
 export { handleFileSync, uploadAllFiles } from './file-sync'
-export { createSocketConnection, disconnectSocket, cancelSocketConnect, requestSyncAll, getSocket, type SocketConnectOptions } from './socket-client'
+export { createSocketConnection, disconnectSocket, cancelSocketConnect, requestSyncAll, requestListDir, requestFetchFile, getSocket, type SocketConnectOptions } from './socket-client'
@@ -1,10 +1,20 @@
 import type { FileChangeEvent } from '../types'
 import type { Settings } from '../utils'
+import { getDeviceIdentity } from '../utils'
 
 // 存储 SocketManager 和 SocketIOClient 引用
 let manager: SocketManager | null = null
 let socket: SocketIOClient | null = null
 
+// 握手信息：版本与 script.json 保持一致，能力列表只声明脚本已实现的功能
+const CLIENT_VERSION = '1.1.4'
+const PROTOCOL_VERSION = 2
+const CLIENT_CAPABILITIES = ['utf8', 'binary', 'sync_path', 'list_dir', 'request_upload', 'ack', 'resume']
+// 服务端 hello 回复的协商能力（旧版服务端不回复，保持为空）
+let negotiated: string[] = []
+// 服务端分配的会话 id，断线自动重连时带上以恢复会话；新建连接时清空
+let sessionId: string | null = null
+
 /**
  * Socket.IO 连接选项
  */
@@ -50,6 +60,7 @@ export const createSocketConnection = (options: SocketConnectOptions): void => {
         manager = null
     }
 
+    sessionId = null
     setConnected(false)
     setConnecting(true)
     setErrorMessage('')
@@ -69,17 +80,55 @@ export const createSocketConnection = (options: SocketConnectOptions): void => {
         socket = manager.defaultSocket
         socketRef.current = socket
 
+        // 发送配置与握手；连接成功、以及等待确认的连接被电脑端允许后都会调用
+        const sendHandshake = () => {
+            negotiated = []
+            socket?.emit('configure', {
+                enableFileSizeLimit: settings.enableFileSizeLimit,
+                maxFileSize: settings.maxFileSize,
+                pathRegex: settings.pathRegex,
+                // 声明可解码的编码，服务端对 UTF-8 文本直接发送纯文本，其它文件以二进制附件发送
+                // 不声明 compression：Data 只支持 lzfse/lz4/lzma/zlib，无法解压服务端的 zstd/gzip
+                encodings: ['base64', 'utf8', 'binary']
+            })
+
+            // 版本与能力握手（旧版服务端会忽略）
+            socket?.emit('hello', {
+                clientVersion: CLIENT_VERSION,
+                protocol: PROTOCOL_VERSION,
+                capabilities: CLIENT_CAPABILITIES,
+                sessionId: sessionId ?? undefined,
+                ...getDeviceIdentity()
+            })
+        }
+
         socket.on('connect', (data: any[]) => {
             setConnected(true)
             setConnecting(false)
             addLog('连接', '成功', 'success', '已连接到服务器')
+            sendHandshake()
+        })
 
-            // 发送配置
-            socket?.emit('configure', {
-                enableFileSizeLimit: settings.enableFileSizeLimit,
-                maxFileSize: settings.maxFileSize,
-                pathRegex: settings.pathRegex
-            })
+        // 已有其他客户端连接，电脑端选择了“询问”
+        socket.on('takeover_pending', (data: any[]) => {
+            const info = Array.isArray(data) ? data[0] : data
+            addLog('连接', '等待确认', 'warning', info?.message || '等待电脑端确认')
+        })
+
+        socket.on('takeover_accepted', (data: any[]) => {
+            addLog('连接', '已确认', 'success', '电脑端已允许本设备连接')
+            sendHandshake()
+        })
+
+        // 电脑端开启了设备确认，本设备尚未被信任
+        socket.on('device_pending', (data: any[]) => {
+            const info = Array.isArray(data) ? data[0] : data
+            addLog('连接', '等待确认', 'warning', info?.message || '等待电脑端确认此设备')
+        })
+
+        socket.on('device_approved', (data: any[]) => {
+            addLog('连接', '已确认', 'success', '电脑端已允许本设备连接')
+            sendHandshake()
         })
 
         socket.on('disconnect', (data: any[]) => {
@@ -116,10 +165,16 @@ export const createSocketConnection = (options: SocketConnectOptions): void => {
         socket.on('connection_rejected', (data: any[]) => {
             const error = Array.isArray(data) ? data[0] : data
             console.error('[Socket.IO] 连接被拒绝:', error)
-            const errorMsg = error?.message || '连接被服务端拒绝'
+            const errorMsg = [error?.message || '连接被服务端拒绝', error?.upgradeHint].filter(Boolean).join('，')
             setErrorMessage(errorMsg)
             setConnecting(false)
             addLog('连接', '拒绝', 'error', errorMsg)
+            // 被拒绝/被替换/被电脑端断开时不再自动重连，避免与新连接互相挤占
+            manager?.disconnect()
+            manager = null
+            socket = null
+            socketRef.current = null
+            setConnected(false)
         })
 
         // 监听各种事件并转发到 onMessage
@@ -127,7 +182,8 @@ export const createSocketConnection = (options: SocketConnectOptions): void => {
             'update', 'delete', 'create_dir',
             'chunk_start', 'chunk_data', 'chunk_complete', 'chunk_ack',
             'sync_start', 'sync_complete', 'sync_error',
-            'server_log'
+            'server_log', 'request_upload', 'hello',
+            'dir_listing', 'fetch_error'
         ]
 
         events.forEach(event => {
@@ -135,7 +191,15 @@ export const createSocketConnection = (options: SocketConnectOptions): void => {
                 const logData = Array.isArray(data) ? (data[0] || {}) : (data || {})
 
                 const eventData = Array.isArray(data) ? (data[0] || {}) : (data || {})
-                onMessage({ ...eventData, action: event } as FileChangeEvent)
+                // binary 编码的内容以 Socket.IO 二进制附件的形式跟在第一个参数之后
+                const binary = Array.isArray(data) ? data.slice(1).find(item => item instanceof Data) : undefined
+                if (event === 'hello') {
+                    negotiated = eventData.negotiated ?? []
+                    sessionId = eventData.sessionId ?? null
+                }
+                // 协商了 ack 时，分片通过 Socket.IO 原生确认回复，否则发送 chunk_ack 事件
+                const nativeAck = event === 'chunk_data' && negotiated.includes('ack') ? ack : undefined
+                onMessage({ ...eventData, action: event, binary, ack: nativeAck } as FileChangeEvent)
             })
         })
 
@@ -209,6 +273,25 @@ export const requestSyncAll = (
     }
 }
 
+/**
+ * 浏览电脑端目录，结果以 dir_listing 事件返回
+ */
+export const requestListDir = (socketRef: { current: SocketIOClient | null }, path: string): void => {
+    socketRef.current?.emit('list_dir', { path })
+}
+
+/**
+ * 按需获取电脑端的单个文件，走 update/分片流程；失败时收到 fetch_error
+ */
+export const requestFetchFile = (socketRef: { current: SocketIOClient | null }, path: string): void => {
+    socketRef.current?.emit('fetch_file', { path })
+}
+
+/**
+ * 服务端 hello 协商结果中是否包含指定能力
+ */
+export const peerSupports = (capability: string): boolean => negotiated.includes(capability)
+
 /**
  * 获取当前 socket
  */
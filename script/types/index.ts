@@ -13,21 +13,43 @@ export type SyncLog = {
  * 文件变更事件类型
  */
 export type FileChangeEvent = {
-    action: 'update' | 'delete' | 'create_dir' | 'sync_start' | 'sync_complete' | 'sync_error' | 'server_log'
+    action: 'update' | 'delete' | 'create_dir' | 'sync_start' | 'sync_complete' | 'sync_error' | 'server_log' | 'request_upload'
+    | 'dir_listing' | 'fetch_error' | 'hello'
     | 'chunk_start' | 'chunk_data' | 'chunk_ack' | 'chunk_complete'
     path: string
     content: string | null
     isDir: boolean
     encoding?: string
+    binary?: Data      // encoding 为 binary 时的二进制附件
     status?: 'success' | 'error' | 'warning'
     message?: string
+    scope?: string[]   // sync_start/sync_complete：sync_path 的同步范围
     // 分片相关字段
     fileId?: string
     totalChunks?: number
     totalSize?: number
     chunkIndex?: number
     success?: boolean  // ACK 成功标志
-    error?: string     // ACK 错误信息
+    error?: string     // ACK 错误信息 / dir_listing 失败原因
+    entries?: RemoteEntry[]  // dir_listing
+    serverVersion?: string   // hello
+    negotiated?: string[]    // hello：双方都支持的能力
+    sessionId?: string       // hello：会话 id（协商了 resume 时）
+    resumed?: boolean        // hello：是否恢复了断线前的会话
+    ack?: (value?: any) => void  // chunk_data：协商了 ack 时的原生确认回调
+}
+
+/**
+ * 电脑端目录条目（list_dir 响应）
+ */
+export type RemoteEntry = {
+    name: string
+    path: string
+    isDir: boolean
+    size: number
+    mtime: number | null
+    filtered: boolean
+    reason: string | null
 }
 
 /**
@@ -42,6 +64,7 @@ export type ChunkReceiveState = {
     path: string
     receivedChunks: number
     totalChunks: number
+    encoding?: string
 }
 
 /**
@@ -1,3 +1,3 @@
 export { shouldFilterPath } from './path'
 export { createStorageManager, createUnifiedStorage, UnifiedStorage, StorageManager } from './storage'
-export { getCurrentSettings, saveSettings, STORAGE_KEYS, type Settings } from './skinSync-service'
+export { getCurrentSettings, getDeviceIdentity, saveSettings, STORAGE_KEYS, type Settings } from './skinSync-service'
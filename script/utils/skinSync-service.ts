@@ -44,6 +44,7 @@ export const STORAGE_KEYS = {
   CACHE_DATA: 'cacheData',
   LAST_UPDATE: 'lastUpdate',
   LAST_VERSION: 'lastVersion',
+  DEVICE_ID: 'deviceId',
 }
 
 /**
@@ -75,6 +76,18 @@ export const saveSettings = (settings: Settings) => {
 }
 
 
+/**
+ * 获取本设备的身份：id 首次调用时生成并持久化，电脑端据此识别已信任的设备
+ */
+export const getDeviceIdentity = () => {
+  let deviceId = storageManager.storage.get<string>(STORAGE_KEYS.DEVICE_ID)
+  if (!deviceId) {
+    deviceId = `${Date.now().toString(36)}${Math.random().toString(36).slice(2, 10)}`
+    storageManager.storage.set(STORAGE_KEYS.DEVICE_ID, deviceId)
+  }
+  return { deviceId, deviceName: Device.model }
+}
+
 /**
  * 解析URL参数的简单函数
  * @param url URL字符串